@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::challenge::Answer;
+use crate::error::Error;
+use crate::input;
+
+/// Recorded answers for a single day, stored alongside its inputs in
+/// `<input dir>/<day>/answers` as one `<part>: <answer>` line per part.
+///
+/// Multi-line answers are stored on a single line with newlines escaped.
+pub struct Answers {
+    path: PathBuf,
+    recorded: BTreeMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    Fail(String),
+    New,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Pass => write!(f, "PASS"),
+            Self::Fail(_) => write!(f, "FAIL"),
+            Self::New => write!(f, "NEW"),
+        }
+    }
+}
+
+impl Answers {
+    /// Loads the answers recorded for `day`. A day without an answers file
+    /// has simply not recorded anything yet.
+    pub fn load(dir: &Path, day: u32) -> Result<Self, Error> {
+        let path = input::path(dir, &day.to_string(), "answers");
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(Error::input(path, e)),
+        };
+        let recorded = parse(&text).map_err(|line_no| {
+            Error::parse(
+                format!("Failed to load answers from {}", path.display()),
+                MalformedAnswer(line_no + 1),
+            )
+        })?;
+        Ok(Self { path, recorded })
+    }
+
+    pub fn check(&self, part: &str, answer: &Answer) -> Verdict {
+        match self.recorded.get(part) {
+            None => Verdict::New,
+            Some(expected) if *expected == answer.to_string() => Verdict::Pass,
+            Some(expected) => Verdict::Fail(expected.clone()),
+        }
+    }
+
+    pub fn record(&mut self, part: &str, answer: &Answer) {
+        self.recorded.insert(part.to_owned(), answer.to_string());
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        fs::write(&self.path, render(&self.recorded)).map_err(|e| Error::input(&self.path, e))
+    }
+}
+
+#[derive(Debug)]
+struct MalformedAnswer(usize);
+
+impl std::error::Error for MalformedAnswer {}
+impl fmt::Display for MalformedAnswer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {} is not of the form `<part>: <answer>`", self.0)
+    }
+}
+
+fn parse(text: &str) -> Result<BTreeMap<String, String>, usize> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(line_no, line)| {
+            let (part, answer) = line.split_once(": ").ok_or(line_no)?;
+            Ok((part.trim().to_owned(), unescape(answer)))
+        })
+        .collect()
+}
+
+fn render(recorded: &BTreeMap<String, String>) -> String {
+    recorded
+        .iter()
+        .map(|(part, answer)| format!("{}: {}\n", part, escape(answer)))
+        .collect()
+}
+
+fn escape(answer: &str) -> String {
+    answer.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(answer: &str) -> String {
+    let mut out = String::with_capacity(answer.len());
+    let mut chars = answer.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut recorded = BTreeMap::new();
+        recorded.insert("one".to_owned(), "3478233".to_owned());
+        recorded.insert("two".to_owned(), "#.\\\n.#".to_owned());
+
+        let text = render(&recorded);
+        assert_eq!("one: 3478233\ntwo: #.\\\\\\n.#\n", text);
+        assert_eq!(Ok(recorded), parse(&text));
+    }
+
+    #[test]
+    fn test_check() {
+        let answers = Answers {
+            path: PathBuf::new(),
+            recorded: parse("# day 1\none: 12\n").expect("parse failed"),
+        };
+        assert_eq!(Verdict::Pass, answers.check("one", &Answer::Int(12)));
+        assert_eq!(
+            Verdict::Fail("12".to_owned()),
+            answers.check("one", &Answer::Int(13))
+        );
+        assert_eq!(Verdict::New, answers.check("two", &Answer::Int(12)));
+        assert_eq!(Err(0), parse("one 12"));
+    }
+}
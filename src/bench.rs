@@ -0,0 +1,100 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::challenge::Answer;
+
+/// Summary statistics over repeated timings of one phase of a part.
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub min: Duration,
+    pub median: Duration,
+    pub mean: Duration,
+    pub max: Duration,
+    pub std_dev: Duration,
+}
+
+impl Stats {
+    pub fn new(samples: &[Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        let n = sorted.len();
+        let median = if n.is_multiple_of(2) {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2
+        } else {
+            sorted[n / 2]
+        };
+
+        let secs: Vec<f64> = sorted.iter().map(Duration::as_secs_f64).collect();
+        let mean = secs.iter().sum::<f64>() / n as f64;
+        let variance = secs.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n as f64;
+
+        Some(Self {
+            min: sorted[0],
+            median,
+            mean: Duration::from_secs_f64(mean),
+            max: sorted[n - 1],
+            std_dev: Duration::from_secs_f64(variance.sqrt()),
+        })
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "min {:.2?}  median {:.2?}  mean {:.2?}  max {:.2?}  std dev {:.2?}",
+            self.min, self.median, self.mean, self.max, self.std_dev
+        )
+    }
+}
+
+/// Timings for a part run repeatedly, split into loading and parsing the
+/// input and solving over the parsed input.
+pub struct Bench {
+    pub day: u32,
+    pub part: String,
+    pub runs: usize,
+    pub answer: Answer,
+    pub prepare: Stats,
+    pub solve: Stats,
+}
+
+impl fmt::Display for Bench {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "day {} part {} ({} runs): {}",
+            self.day, self.part, self.runs, self.answer
+        )?;
+        writeln!(f, "  prepare  {}", self.prepare)?;
+        writeln!(f, "  solve    {}", self.solve)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let samples: Vec<Duration> = [4, 2, 8, 6]
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .collect();
+        let stats = Stats::new(&samples).expect("no stats");
+        assert_eq!(Duration::from_millis(2), stats.min);
+        assert_eq!(Duration::from_millis(5), stats.median);
+        assert_eq!(Duration::from_millis(5), stats.mean);
+        assert_eq!(Duration::from_millis(8), stats.max);
+        assert_eq!(2236, stats.std_dev.as_micros());
+
+        let stats = Stats::new(&samples[..3]).expect("no stats");
+        assert_eq!(Duration::from_millis(4), stats.median);
+
+        assert_eq!(None, Stats::new(&[]));
+    }
+}
@@ -1,19 +1,158 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::error::Error;
+use crate::input;
+
+/// A day's solutions, declared by the day's module and registered with the
+/// runner through `Day::of`.
 pub trait Challenge {
-    fn run(&mut self, part: &str) -> ChallengeResult;
+    /// The advent calendar day this challenge solves.
+    const DAY: u32;
+
+    /// The puzzle's title, shown in the list of registered days.
+    const TITLE: &'static str;
+
+    /// The day's parts, in the order they should run.
+    const PARTS: &'static [Part];
+}
+
+/// A single part of a day's puzzle.
+pub struct Part {
+    pub name: &'static str,
+    /// The names of the `--param`s the part reads. Runs given any other
+    /// parameter are refused, so a misspelled name is not silently ignored.
+    pub params: &'static [&'static str],
+    /// Loads and parses the input for the part, returning a solver over the
+    /// parsed input so that the two phases can be timed separately.
+    pub prepare: fn(&input::Source, &input::Params) -> Result<Solver, Error>,
+}
+
+/// The registry entry for a `Challenge`.
+#[derive(Clone, Copy)]
+pub struct Day {
+    pub number: u32,
+    pub title: &'static str,
+    pub parts: &'static [Part],
+}
+
+impl Day {
+    pub fn of<C: Challenge>() -> Self {
+        Self {
+            number: C::DAY,
+            title: C::TITLE,
+            parts: C::PARTS,
+        }
+    }
+
+    pub fn part(&self, name: &str) -> Result<&'static Part, Error> {
+        self.parts
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| Error::MissingPart {
+                day: self.number,
+                part: name.to_owned(),
+                available: self.part_names().join(", "),
+            })
+    }
+
+    pub fn part_names(&self) -> Vec<&'static str> {
+        self.parts.iter().map(|p| p.name).collect()
+    }
+
+    pub fn run(
+        &self,
+        part: &str,
+        source: &input::Source,
+        params: &input::Params,
+    ) -> ChallengeResult {
+        (self.part(part)?.prepare)(source, params).and_then(|solve| solve())
+    }
 }
 
-pub enum Err {
-    MissingPart(String),
-    Failure(String),
+/// Solves a part over input that has already been loaded. Solvers may be
+/// called repeatedly and must produce the same answer each time.
+pub type Solver = Box<dyn Fn() -> ChallengeResult>;
+
+/// The result of running a single part of a challenge.
+///
+/// Parts hand their answer back to the caller rather than printing it, so
+/// the runner decides how it is displayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Answer {
+    Int(i64),
+    Text(String),
+    Lines(Vec<String>),
 }
 
-impl ToString for Err {
-    fn to_string(&self) -> String {
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::MissingPart(s) => s.clone(),
-            Self::Failure(s) => s.clone(),
+            Self::Int(n) => write!(f, "{}", n),
+            Self::Text(s) => write!(f, "{}", s),
+            Self::Lines(lines) => write!(f, "{}", lines.join("\n")),
         }
     }
 }
 
-pub type ChallengeResult = Result<(), Err>;
+impl From<i64> for Answer {
+    fn from(n: i64) -> Self {
+        Self::Int(n)
+    }
+}
+
+impl From<u64> for Answer {
+    fn from(n: u64) -> Self {
+        i64::try_from(n)
+            .map(Self::Int)
+            .unwrap_or_else(|_| Self::Text(n.to_string()))
+    }
+}
+
+impl From<usize> for Answer {
+    fn from(n: usize) -> Self {
+        Self::from(n as u64)
+    }
+}
+
+impl From<String> for Answer {
+    fn from(s: String) -> Self {
+        Self::Text(s)
+    }
+}
+
+impl From<&str> for Answer {
+    fn from(s: &str) -> Self {
+        Self::Text(s.to_owned())
+    }
+}
+
+impl From<Vec<String>> for Answer {
+    fn from(lines: Vec<String>) -> Self {
+        Self::Lines(lines)
+    }
+}
+
+pub type ChallengeResult = Result<Answer, Error>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_answer_from() {
+        assert_eq!(Answer::Int(42), Answer::from(42u64));
+        assert_eq!(Answer::Int(-3), Answer::from(-3i64));
+        assert_eq!(Answer::Text(u64::MAX.to_string()), Answer::from(u64::MAX));
+    }
+
+    #[test]
+    fn test_answer_display() {
+        assert_eq!("12", Answer::Int(12).to_string());
+        assert_eq!("abc", Answer::from("abc").to_string());
+        assert_eq!(
+            "#.#\n.#.",
+            Answer::from(vec!["#.#".to_owned(), ".#.".to_owned()]).to_string()
+        );
+    }
+}
@@ -0,0 +1,247 @@
+/// What the command line asked the runner to do.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    All,
+    Day(String),
+    Part(String, String),
+    /// Disassemble the Intcode program in a file, or stdin when `-`.
+    Disasm(String),
+    /// Step through the Intcode program in a file, reading commands from stdin.
+    Debug(String),
+    /// Run the Intcode program in the first file, tracing it to the second.
+    Trace(String, String),
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Write the answers produced by this run to each day's answers file.
+    pub record: bool,
+    /// Run a single part this many times and report timing statistics.
+    pub bench: Option<usize>,
+    /// Read every part's input from this file, or from stdin when `-`.
+    pub input: Option<String>,
+    /// `<name>=<value>` parameters passed to every part.
+    pub params: Vec<String>,
+    /// Only disassemble instructions reachable by following jumps.
+    pub follow: bool,
+    /// Only trace instructions in this `<start>-<end>` address range.
+    pub addresses: Option<String>,
+    /// Only trace instructions with these mnemonics.
+    pub ops: Vec<String>,
+    /// Stop a traced or debugged program after this many instructions.
+    pub budget: Option<u64>,
+}
+
+pub const USAGE: &str =
+    "usage: aoc2k19 [--record] [--bench <runs>] [--input <path> | -] [--param <name>=<value>]... (help | all | <day> all | <day> <part> | disasm [--follow] <path> | debug [--budget <steps>] <path> | trace [--budget <steps>] [--addresses <start>-<end>] [--op <mnemonic>]... <path> <file>)";
+
+pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<(Command, Options), String> {
+    let mut options = Options::default();
+    let mut positional = vec![];
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "--help" | "-h" => return Ok((Command::Help, options)),
+            "--record" => options.record = true,
+            "--follow" => options.follow = true,
+            "--bench" => {
+                let runs = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|n| *n > 0)
+                    .ok_or("--bench requires a positive number of runs")?;
+                options.bench = Some(runs);
+            }
+            "--budget" => {
+                let steps = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("--budget requires a number of steps")?;
+                options.budget = Some(steps);
+            }
+            "--param" => {
+                options
+                    .params
+                    .push(args.next().ok_or("--param requires <name>=<value>")?);
+            }
+            "--addresses" => {
+                options.addresses = Some(args.next().ok_or("--addresses requires <start>-<end>")?);
+            }
+            "--op" => options
+                .ops
+                .push(args.next().ok_or("--op requires a mnemonic")?),
+            "--input" => {
+                options.input = Some(
+                    args.next()
+                        .ok_or("--input requires a path, or - for stdin")?,
+                );
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown flag {}", flag)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let command = match &positional[..] {
+        [help] if help == "help" => Command::Help,
+        [all] if all == "all" => Command::All,
+        [disasm, path] if disasm == "disasm" => Command::Disasm(path.clone()),
+        [debug, path] if debug == "debug" => Command::Debug(path.clone()),
+        [trace, path, file] if trace == "trace" => Command::Trace(path.clone(), file.clone()),
+        [day, all] if all == "all" => Command::Day(day.clone()),
+        [day, part] => Command::Part(day.clone(), part.clone()),
+        _ => return Err(USAGE.to_owned()),
+    };
+    if options.bench.is_some() && !matches!(command, Command::Part(_, _)) {
+        return Err("--bench runs a single `<day> <part>`".to_owned());
+    }
+    if options.bench.is_some() && options.record {
+        return Err("--bench does not record answers, drop --record".to_owned());
+    }
+    if options.follow && !matches!(command, Command::Disasm(_)) {
+        return Err("--follow only applies to disasm".to_owned());
+    }
+    if (options.addresses.is_some() || !options.ops.is_empty())
+        && !matches!(command, Command::Trace(_, _))
+    {
+        return Err("--addresses and --op only apply to trace".to_owned());
+    }
+    if options.budget.is_some() && !matches!(command, Command::Debug(_) | Command::Trace(_, _)) {
+        return Err("--budget only applies to debug and trace".to_owned());
+    }
+    if (options.record || options.input.is_some() || !options.params.is_empty())
+        && matches!(
+            command,
+            Command::Disasm(_) | Command::Debug(_) | Command::Trace(_, _)
+        )
+    {
+        return Err(
+            "--record, --input and --param do not apply to disasm, debug or trace".to_owned(),
+        );
+    }
+    Ok((command, options))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(s: &str) -> impl Iterator<Item = String> + '_ {
+        s.split_whitespace().map(str::to_owned)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Ok((
+                Command::All,
+                Options {
+                    record: true,
+                    ..Options::default()
+                }
+            )),
+            parse(args("all --record"))
+        );
+        assert_eq!(
+            Ok((Command::Day("two".to_owned()), Options::default())),
+            parse(args("two all"))
+        );
+        assert_eq!(
+            Ok((
+                Command::Part("two".to_owned(), "one".to_owned()),
+                Options {
+                    record: true,
+                    ..Options::default()
+                }
+            )),
+            parse(args("--record two one"))
+        );
+        assert_eq!(Err(USAGE.to_owned()), parse(args("two")));
+        assert_eq!(Ok((Command::Help, Options::default())), parse(args("help")));
+        assert_eq!(
+            Ok((Command::Help, Options::default())),
+            parse(args("1 --help"))
+        );
+        assert_eq!(
+            Ok((
+                Command::Part("three".to_owned(), "one".to_owned()),
+                Options {
+                    bench: Some(10),
+                    ..Options::default()
+                }
+            )),
+            parse(args("three one --bench 10"))
+        );
+        assert_eq!(
+            Ok((
+                Command::Day("1".to_owned()),
+                Options {
+                    input: Some("-".to_owned()),
+                    ..Options::default()
+                }
+            )),
+            parse(args("--input - 1 all"))
+        );
+        assert_eq!(
+            Ok((
+                Command::Part("2".to_owned(), "2".to_owned()),
+                Options {
+                    params: vec!["target=100".to_owned(), "x=1".to_owned()],
+                    ..Options::default()
+                }
+            )),
+            parse(args("--param target=100 2 2 --param x=1"))
+        );
+        assert_eq!(
+            Ok((
+                Command::Disasm("inputs/2/1.txt".to_owned()),
+                Options {
+                    follow: true,
+                    ..Options::default()
+                }
+            )),
+            parse(args("disasm --follow inputs/2/1.txt"))
+        );
+        assert_eq!(
+            Ok((Command::Debug("prog.txt".to_owned()), Options::default())),
+            parse(args("debug prog.txt"))
+        );
+        assert_eq!(
+            Ok((
+                Command::Trace("prog.txt".to_owned(), "out.txt".to_owned()),
+                Options {
+                    addresses: Some("4-20".to_owned()),
+                    ops: vec!["ADD".to_owned(), "MUL".to_owned()],
+                    ..Options::default()
+                }
+            )),
+            parse(args(
+                "trace --addresses 4-20 --op ADD --op MUL prog.txt out.txt"
+            ))
+        );
+        assert!(parse(args("--op ADD 1 all")).is_err());
+        assert!(parse(args("--follow 1 all")).is_err());
+        assert!(parse(args("--follow debug prog.txt")).is_err());
+        assert_eq!(
+            Ok((
+                Command::Debug("prog.txt".to_owned()),
+                Options {
+                    budget: Some(500),
+                    ..Options::default()
+                }
+            )),
+            parse(args("debug --budget 500 prog.txt"))
+        );
+        assert!(parse(args("--budget 500 1 all")).is_err());
+        assert!(parse(args("disasm --budget 500 prog.txt")).is_err());
+        assert!(parse(args("trace prog.txt out.txt --budget")).is_err());
+        assert!(parse(args("--record disasm prog.txt")).is_err());
+        assert!(parse(args("--input - debug prog.txt")).is_err());
+        assert!(parse(args("trace prog.txt out.txt --param x=1")).is_err());
+        assert!(parse(args("--bogus all")).is_err());
+        assert!(parse(args("1 all --input")).is_err());
+        assert!(parse(args("--bench 0 three one")).is_err());
+        assert!(parse(args("--bench 10 all")).is_err());
+        assert!(parse(args("--bench 10 --record three one")).is_err());
+    }
+}
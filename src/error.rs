@@ -0,0 +1,168 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Every way running a challenge can fail.
+///
+/// `Display` prints the whole chain of causes, so reporting an `Error` to the
+/// user only ever needs `{}`.
+#[derive(Debug)]
+pub enum Error {
+    /// The command line could not be understood.
+    Usage(String),
+    MissingDay {
+        day: String,
+        registered: String,
+    },
+    MissingPart {
+        day: u32,
+        part: String,
+        available: String,
+    },
+    /// Reading an input file or reading or writing an answers file failed.
+    /// `path` is `None` for stdin.
+    Input {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// Input was read but could not be understood.
+    Parse {
+        context: String,
+        source: Box<dyn StdError>,
+    },
+    /// A solution failed while running over well-formed input.
+    Runtime {
+        context: String,
+        source: Option<Box<dyn StdError>>,
+    },
+}
+
+impl Error {
+    pub fn input<P: Into<PathBuf>>(path: P, source: io::Error) -> Self {
+        Self::Input {
+            path: Some(path.into()),
+            source,
+        }
+    }
+
+    pub fn parse<S: Into<String>, E: StdError + 'static>(context: S, source: E) -> Self {
+        Self::Parse {
+            context: context.into(),
+            source: Box::new(source),
+        }
+    }
+
+    pub fn runtime<S: Into<String>>(context: S) -> Self {
+        Self::Runtime {
+            context: context.into(),
+            source: None,
+        }
+    }
+
+    pub fn runtime_from<S: Into<String>, E: StdError + 'static>(context: S, source: E) -> Self {
+        Self::Runtime {
+            context: context.into(),
+            source: Some(Box::new(source)),
+        }
+    }
+
+    /// The process exit code for this kind of failure.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Usage(_) => 2,
+            Self::MissingDay { .. } => 3,
+            Self::MissingPart { .. } => 4,
+            Self::Input { .. } => 5,
+            Self::Parse { .. } => 6,
+            Self::Runtime { .. } => 7,
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Input { source, .. } => Some(source),
+            Self::Parse { source, .. } => Some(source.as_ref()),
+            Self::Runtime {
+                source: Some(source),
+                ..
+            } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Usage(usage) => write!(f, "{}", usage)?,
+            Self::MissingDay { day, registered } => write!(
+                f,
+                "No challenge for day {} has been registered. Registered days:\n{}",
+                day, registered
+            )?,
+            Self::MissingPart {
+                day,
+                part,
+                available,
+            } => write!(
+                f,
+                "Day {} has no part {}. Available parts: {}",
+                day, part, available
+            )?,
+            Self::Input { path: Some(p), .. } => write!(f, "I/O failure on {}", p.display())?,
+            Self::Input { path: None, .. } => write!(f, "Failed to read stdin")?,
+            Self::Parse { context, .. } => write!(f, "{}", context)?,
+            Self::Runtime { context, .. } => write!(f, "{}", context)?,
+        }
+
+        let mut source = self.source();
+        while let Some(cause) = source {
+            write!(f, ": {}", cause)?;
+            source = cause.source();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Inner;
+
+    impl StdError for Inner {}
+    impl fmt::Display for Inner {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "inner")
+        }
+    }
+
+    #[derive(Debug)]
+    struct Outer(Inner);
+
+    impl StdError for Outer {
+        fn source(&self) -> Option<&(dyn StdError + 'static)> {
+            Some(&self.0)
+        }
+    }
+    impl fmt::Display for Outer {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "outer")
+        }
+    }
+
+    #[test]
+    fn test_display_chain() {
+        let e = Error::parse("Failed to parse day 9", Outer(Inner));
+        assert_eq!("Failed to parse day 9: outer: inner", e.to_string());
+        assert_eq!(6, e.exit_code());
+
+        let e = Error::runtime("Wires never cross");
+        assert_eq!("Wires never cross", e.to_string());
+        assert!(e.source().is_none());
+    }
+}
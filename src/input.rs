@@ -1,29 +1,469 @@
-use std::fs::File;
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error as StdError;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
 use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use crate::challenge;
+use crate::error::Error;
 
-pub fn file(challenge: &str, part: &str) -> io::Result<File> {
-    File::open(format!(
-        "inputs/{challenge:}/{part:}",
-        challenge = challenge,
-        part = part
-    ))
+/// Where challenges read their puzzle input from.
+pub enum Source {
+    /// A directory laid out as `<dir>/<challenge>/<part>`.
+    Dir(PathBuf),
+    /// A single file used as the input of every part.
+    File(PathBuf),
+    /// Input read up front, e.g. from stdin, used for every part.
+    Text(String),
 }
 
-pub fn string(challenge: &str, part: &str) -> io::Result<String> {
-    file(challenge, part).and_then(|mut f| {
+impl Source {
+    /// The directory named by `AOC_INPUT_DIR`, falling back to the `inputs`
+    /// directory of the crate so runs don't depend on the working directory.
+    pub fn from_env() -> Self {
+        Self::from_dir_var(env::var_os("AOC_INPUT_DIR"))
+    }
+
+    /// The directory `var` names, or the crate's `inputs` directory when it
+    /// is unset, as `from_env` reads `AOC_INPUT_DIR`.
+    pub fn from_dir_var(var: Option<OsString>) -> Self {
+        let dir = var
+            .map(PathBuf::from)
+            .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("inputs"));
+        Self::Dir(dir)
+    }
+
+    /// Reads all of stdin, so that it can be shared by repeated runs.
+    pub fn stdin() -> Result<Self, Error> {
         let mut buf = String::new();
-        f.read_to_string(&mut buf).map(|_| buf)
-    })
-}
-
-pub fn read_error(challenge: &str, part: &str, filename: &str, e: io::Error) -> challenge::Err {
-    challenge::Err::Failure(format!(
-        "Failed to read input file {filename:} for part {part:} of challenge {challenge:}: {err:}",
-        filename = filename,
-        challenge = challenge,
-        part = part,
-        err = e.to_string()
-    ))
+        io::stdin()
+            .read_to_string(&mut buf)
+            .map(|_| Self::Text(buf))
+            .map_err(|source| Error::Input { path: None, source })
+    }
+
+    /// The directory inputs are looked up in, if inputs come from one.
+    pub fn dir(&self) -> Option<&Path> {
+        match self {
+            Self::Dir(dir) => Some(dir),
+            _ => None,
+        }
+    }
+
+    pub fn read(&self, challenge: &str, part: &str) -> Result<Input, Error> {
+        let read = |p: PathBuf| match fs::read_to_string(&p) {
+            Ok(text) => Ok(Input::new(p.display().to_string(), text)),
+            Err(e) => Err(Error::input(p, e)),
+        };
+        match self {
+            Self::Dir(dir) => read(path(dir, challenge, part)),
+            Self::File(file) => read(file.clone()),
+            Self::Text(text) => Ok(Input::new("<stdin>", text.clone())),
+        }
+    }
+}
+
+pub fn path(dir: &Path, challenge: &str, part: &str) -> PathBuf {
+    dir.join(challenge).join(part)
+}
+
+/// Named values given on the command line with `--param <name>=<value>`
+/// that let a part be run with something other than its puzzle's constants.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Params(BTreeMap<String, String>);
+
+impl Params {
+    /// Parses `<name>=<value>` assignments.
+    pub fn parse<S: AsRef<str>>(assignments: &[S]) -> Result<Self, Error> {
+        assignments
+            .iter()
+            .map(|a| {
+                a.as_ref()
+                    .split_once('=')
+                    .map(|(name, value)| (name.trim().to_owned(), value.trim().to_owned()))
+                    .ok_or_else(|| {
+                        Error::Usage(format!(
+                            "Parameter {:?} is not of the form <name>=<value>",
+                            a.as_ref()
+                        ))
+                    })
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The names of the parameters given.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    pub fn insert<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) {
+        self.0.insert(name.into(), value.into());
+    }
+
+    /// The value of parameter `name`, or `default` when it was not given.
+    pub fn get<T>(&self, name: &str, default: T) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match self.0.get(name) {
+            None => Ok(default),
+            Some(value) => value.parse().map_err(|e| {
+                Error::Usage(format!(
+                    "Invalid value {:?} for parameter {}: {}",
+                    value, name, e
+                ))
+            }),
+        }
+    }
+}
+
+/// The text of a puzzle input along with where it came from, so parse
+/// failures can point at the file, line and column they happened on.
+///
+/// The parsing helpers skip blank lines and trim surrounding whitespace from
+/// each value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Input {
+    name: String,
+    text: String,
+    first_line: usize,
+}
+
+impl Input {
+    pub fn new<N: Into<String>, T: Into<String>>(name: N, text: T) -> Self {
+        Self {
+            name: name.into(),
+            text: text.into(),
+            first_line: 1,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Parses each line as a single value.
+    pub fn lines<T>(&self) -> Result<Vec<T>, Error>
+    where
+        T: FromStr,
+        T::Err: StdError + 'static,
+    {
+        self.numbered_lines()
+            .map(|(line_no, line)| self.parse_field(line_no, 0, line))
+            .collect()
+    }
+
+    /// Parses comma separated values, which may be spread over several lines.
+    pub fn csv<T>(&self) -> Result<Vec<T>, Error>
+    where
+        T: FromStr,
+        T::Err: StdError + 'static,
+    {
+        self.csv_lines()
+            .map(|rows| rows.into_iter().flatten().collect())
+    }
+
+    /// Parses each line as a row of comma separated values.
+    pub fn csv_lines<T>(&self) -> Result<Vec<Vec<T>>, Error>
+    where
+        T: FromStr,
+        T::Err: StdError + 'static,
+    {
+        self.numbered_lines()
+            .map(|(line_no, line)| {
+                let mut offset = 0;
+                line.split(',')
+                    .map(|field| {
+                        let parsed = self.parse_field(line_no, offset, field);
+                        offset += field.len() + 1;
+                        parsed
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Splits the input into the groups of lines separated by blank lines.
+    pub fn blocks(&self) -> Vec<Input> {
+        let mut blocks = vec![];
+        let mut current: Option<Input> = None;
+        for (index, line) in self.text.lines().enumerate() {
+            let line_no = self.first_line + index;
+            match (&mut current, line.trim().is_empty()) {
+                (Some(_), true) => blocks.extend(current.take()),
+                (None, true) => {}
+                (Some(block), false) => {
+                    block.text.push_str(line);
+                    block.text.push('\n');
+                }
+                (None, false) => {
+                    current = Some(Input {
+                        name: self.name.clone(),
+                        text: format!("{}\n", line),
+                        first_line: line_no,
+                    })
+                }
+            }
+        }
+        blocks.extend(current);
+        blocks
+    }
+
+    /// Parses the input as a rectangle of characters, one row per line.
+    pub fn char_grid(&self) -> Result<Vec<Vec<char>>, Error> {
+        let mut grid: Vec<Vec<char>> = vec![];
+        for (line_no, line) in self.numbered_lines() {
+            let row: Vec<char> = line.trim_end().chars().collect();
+            if let Some(first) = grid.first() {
+                if first.len() != row.len() {
+                    return Err(self.error(
+                        line_no,
+                        0,
+                        line,
+                        RaggedRow {
+                            expected: first.len(),
+                            found: row.len(),
+                        },
+                    ));
+                }
+            }
+            grid.push(row);
+        }
+        Ok(grid)
+    }
+
+    fn numbered_lines(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.text
+            .lines()
+            .enumerate()
+            .map(move |(index, line)| (self.first_line + index, line))
+            .filter(|(_, line)| !line.trim().is_empty())
+    }
+
+    fn parse_field<T>(&self, line_no: usize, offset: usize, field: &str) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: StdError + 'static,
+    {
+        let value = field.trim();
+        let leading = field.len() - field.trim_start().len();
+        value
+            .parse()
+            .map_err(|e| self.error(line_no, offset + leading, value, e))
+    }
+
+    fn error<E: StdError + 'static>(&self, line: usize, offset: usize, value: &str, e: E) -> Error {
+        Error::parse(
+            "Failed to parse input",
+            ParseError {
+                file: self.name.clone(),
+                line,
+                column: offset + 1,
+                value: value.to_owned(),
+                source: Box::new(e),
+            },
+        )
+    }
+}
+
+/// Where in an input a value failed to parse. Lines and columns count from 1.
+#[derive(Debug)]
+pub struct ParseError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub value: String,
+    source: Box<dyn StdError>,
+}
+
+impl StdError for ParseError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {:?}",
+            self.file, self.line, self.column, self.value
+        )
+    }
+}
+
+#[derive(Debug)]
+struct RaggedRow {
+    expected: usize,
+    found: usize,
+}
+
+impl StdError for RaggedRow {}
+impl fmt::Display for RaggedRow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "row is {} wide but the grid is {} wide",
+            self.found, self.expected
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_source_from_dir_var() {
+        let default = Source::from_dir_var(None);
+        let overridden = Source::from_dir_var(Some("/tmp/aoc-inputs".into()));
+        assert_eq!(
+            Some(
+                Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("inputs")
+                    .as_path()
+            ),
+            default.dir()
+        );
+        assert_eq!(Some(Path::new("/tmp/aoc-inputs")), overridden.dir());
+    }
+
+    #[test]
+    fn test_source_read() {
+        let dir = env::temp_dir().join(format!("aoc2k19-source-{}", std::process::id()));
+        fs::create_dir_all(dir.join("2")).expect("Failed to create input dir");
+        fs::write(dir.join("2").join("1.txt"), "1,0,0,0,99").expect("Failed to write input");
+
+        let source = Source::Dir(dir.clone());
+        let input = source.read("2", "1.txt").expect("Failed to read from dir");
+        assert_eq!("1,0,0,0,99", input.text());
+        assert_eq!(
+            dir.join("2").join("1.txt").display().to_string(),
+            input.name()
+        );
+        assert!(matches!(
+            source.read("2", "2.txt"),
+            Err(Error::Input { path: Some(p), .. }) if p == dir.join("2").join("2.txt")
+        ));
+        assert_eq!(Some(dir.as_path()), source.dir());
+
+        // A file is the input of every part, whatever they ask for.
+        let source = Source::File(dir.join("2").join("1.txt"));
+        let input = source.read("9", "answers").expect("Failed to read file");
+        assert_eq!("1,0,0,0,99", input.text());
+        assert_eq!(None, source.dir());
+
+        fs::remove_dir_all(&dir).expect("Failed to clean up");
+        assert!(matches!(
+            source.read("2", "1.txt"),
+            Err(Error::Input { path: Some(_), .. })
+        ));
+
+        let source = Source::Text("12\n14\n".to_owned());
+        let input = source.read("1", "1.txt").expect("Failed to read text");
+        assert_eq!(("<stdin>", "12\n14\n"), (input.name(), input.text()));
+        assert_eq!(None, source.dir());
+    }
+
+    #[test]
+    fn test_params() {
+        let params = Params::parse(&["target=42", " noun = 7 "]).expect("Failed to parse params");
+        assert_eq!(Ok(42), params.get("target", 0).map_err(|e| e.to_string()));
+        assert_eq!(Ok(7), params.get("noun", 0).map_err(|e| e.to_string()));
+        assert_eq!(Ok(3), params.get("verb", 3).map_err(|e| e.to_string()));
+        assert!(params.get::<u8>("target", 0).is_ok());
+        assert!(params.get::<bool>("target", false).is_err());
+        assert!(Params::parse(&["target"]).is_err());
+    }
+
+    #[test]
+    fn test_lines() {
+        let input = Input::new("masses", "12\n14\n\n1969\n");
+        assert_eq!(
+            Ok(vec![12, 14, 1969]),
+            input.lines::<u64>().map_err(|e| e.to_string())
+        );
+
+        let input = Input::new("masses", "12\n\n  x4\n");
+        assert_eq!(
+            Err(
+                "Failed to parse input: masses:3:3: \"x4\": invalid digit found in string"
+                    .to_owned()
+            ),
+            input.lines::<u64>().map_err(|e| e.to_string())
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        let input = Input::new("program", "1,0,0,\n3,99\n");
+        assert!(input.csv::<usize>().is_err());
+
+        let input = Input::new("program", "1,0, 0\n3,99\n");
+        assert_eq!(
+            Ok(vec![1, 0, 0, 3, 99]),
+            input.csv::<usize>().map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(vec![vec![1, 0, 0], vec![3, 99]]),
+            input.csv_lines::<usize>().map_err(|e| e.to_string())
+        );
+
+        let input = Input::new("program", "1,0\n3, 9x9\n");
+        assert_eq!(
+            Err(
+                "Failed to parse input: program:2:4: \"9x9\": invalid digit found in string"
+                    .to_owned()
+            ),
+            input.csv::<usize>().map_err(|e| e.to_string())
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let input = Input::new("groups", "a\nb\n\n\nc\n\nx\n");
+        let blocks = input.blocks();
+        assert_eq!(
+            vec!["a\nb\n", "c\n", "x\n"],
+            blocks.iter().map(Input::text).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Err(
+                "Failed to parse input: groups:7:1: \"x\": invalid digit found in string"
+                    .to_owned()
+            ),
+            blocks[2].lines::<u8>().map_err(|e| e.to_string())
+        );
+    }
+
+    #[test]
+    fn test_char_grid() {
+        let input = Input::new("grid", "#.\n.#\n");
+        assert_eq!(
+            Ok(vec![vec!['#', '.'], vec!['.', '#']]),
+            input.char_grid().map_err(|e| e.to_string())
+        );
+
+        let input = Input::new("grid", "#.\n.#.\n");
+        assert_eq!(
+            Err(
+                "Failed to parse input: grid:2:1: \".#.\": row is 3 wide but the grid is 2 wide"
+                    .to_owned()
+            ),
+            input.char_grid().map_err(|e| e.to_string())
+        );
+    }
 }
@@ -0,0 +1,1413 @@
+//! An Intcode computer, shared by every day whose puzzle runs an Intcode
+//! program.
+//!
+//! [`Machine`] is the usual way in: load a program, feed it input, run it and
+//! drain its output, or `resume` it repeatedly to handle each output and
+//! input request as it happens. The lower level [`run_step`] and [`OpCode`]
+//! work on bare [`Registers`] for callers that need to drive execution
+//! themselves. [`asm`] and [`disasm`] translate between Intcode and a
+//! readable assembly, and [`debugger`] steps through a [`Machine`] one
+//! command at a time. [`trace`] records each instruction a program runs,
+//! [`watchdog`] stops programs that never halt, [`snapshot`] saves and
+//! restores a machine's state and [`history`] steps one backwards.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::mem;
+
+use crate::error;
+use crate::input;
+
+use trace::{Event, Tracer};
+use watchdog::Watchdog;
+
+pub mod asm;
+pub mod debugger;
+pub mod disasm;
+pub mod history;
+pub mod snapshot;
+pub mod trace;
+pub mod watchdog;
+
+/// A program loaded into memory along with its instruction pointer,
+/// relative base and queued input and output.
+#[derive(Debug, Clone)]
+pub struct Machine {
+    registers: Registers,
+    state: EvalStep,
+    input: VecDeque<i64>,
+    output: Vec<i64>,
+    watchdog: Watchdog,
+}
+
+impl Machine {
+    pub fn new(registers: Registers) -> Self {
+        Self {
+            registers,
+            state: EvalStep::new(0),
+            input: VecDeque::new(),
+            output: Vec::new(),
+            watchdog: Watchdog::new(),
+        }
+    }
+
+    /// Checks every instruction with `watchdog` before it runs, so a program
+    /// that never halts stops with `Exhausted` or `Looped` instead.
+    pub fn with_watchdog(self, watchdog: Watchdog) -> Self {
+        Self { watchdog, ..self }
+    }
+
+    /// Parses comma separated Intcode, which may span several lines.
+    pub fn from_program(program: &str) -> Result<Self, error::Error> {
+        Registers::from_program(program).map(Self::new)
+    }
+
+    pub fn from_input(input: &input::Input) -> Result<Self, error::Error> {
+        Registers::from_input(input).map(Self::new)
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// The instruction pointer and relative base.
+    pub fn state(&self) -> EvalStep {
+        self.state
+    }
+
+    /// The number of instructions the machine has run, less any a
+    /// `History` has undone.
+    pub fn steps(&self) -> u64 {
+        self.watchdog.steps()
+    }
+
+    pub fn peek(&self, address: usize) -> RegisterResult<i64> {
+        self.registers.at(address).copied()
+    }
+
+    pub fn poke(&mut self, address: usize, value: i64) -> RegisterResult<()> {
+        self.registers.set(address, value)
+    }
+
+    /// Queues `value` for the program's next input instruction.
+    pub fn feed(&mut self, value: i64) {
+        self.input.push_back(value);
+    }
+
+    pub fn feed_all<I: IntoIterator<Item = i64>>(&mut self, values: I) {
+        self.input.extend(values);
+    }
+
+    /// Takes everything `run` has collected from the program's output.
+    pub fn drain_output(&mut self) -> Vec<i64> {
+        mem::take(&mut self.output)
+    }
+
+    /// Executes a single instruction, returning `Output` with the value if
+    /// it was an output instruction. The machine only moves on when the
+    /// instruction succeeds, so a failing, finished or blocked machine stays
+    /// put and retries the same instruction next time.
+    pub fn step(&mut self) -> Run {
+        self.step_traced(&mut ())
+    }
+
+    /// Like `step`, also recording the instruction with `tracer` if it ran.
+    pub fn step_traced(&mut self, tracer: &mut dyn Tracer) -> Run {
+        if let Some(res) = self.watchdog.check(&self.state, &self.registers) {
+            return res;
+        }
+        let inputs = self.input.len();
+        let mut io = Step {
+            input: &mut self.input,
+            output: None,
+        };
+        match run_step_traced(&self.state, &mut self.registers, &mut io, tracer) {
+            Run::Continue(next) => {
+                self.watchdog.ran(io.input.len() < inputs);
+                self.state = next;
+                io.output.map_or(Run::Continue(next), Run::Output)
+            }
+            res => res,
+        }
+    }
+
+    /// Executes instructions until the program produces a value, stops,
+    /// fails or needs input it has not been fed. Driving a machine this way
+    /// hands each output straight to the caller instead of collecting it.
+    pub fn resume(&mut self) -> Run {
+        loop {
+            match self.step() {
+                Run::Continue(_) => {}
+                res => return res,
+            }
+        }
+    }
+
+    /// Executes instructions until the program stops, fails or needs input
+    /// it has not been fed, collecting its output for `drain_output`.
+    pub fn run(&mut self) -> Run {
+        loop {
+            match self.resume() {
+                Run::Output(value) => self.output.push(value),
+                res => return res,
+            }
+        }
+    }
+}
+
+/// The devices for a single machine step, which hold on to any output for
+/// the step to report.
+struct Step<'a> {
+    input: &'a mut VecDeque<i64>,
+    output: Option<i64>,
+}
+
+impl Io for Step<'_> {
+    fn input(&mut self) -> Option<i64> {
+        self.input.pop_front()
+    }
+
+    fn output(&mut self, value: i64) {
+        self.output = Some(value);
+    }
+}
+
+/// Devices that count how many inputs are read through them.
+struct Counted<'a> {
+    io: &'a mut dyn Io,
+    inputs: usize,
+}
+
+impl Io for Counted<'_> {
+    fn input(&mut self) -> Option<i64> {
+        let value = self.io.input();
+        self.inputs += value.is_some() as usize;
+        value
+    }
+
+    fn output(&mut self, value: i64) {
+        self.io.output(value)
+    }
+}
+
+/// Runs the program in `registers` from position 0 until it stops, fails or
+/// tries to read input, which it never has. A program that never halts runs
+/// forever; `run_watched` can stop it.
+pub fn run_script(registers: &mut Registers) -> Run {
+    run_io(registers, &mut ())
+}
+
+/// Runs the program in `registers` from position 0 until it stops, fails or
+/// runs out of input, reading and writing through `io`.
+pub fn run_io(registers: &mut Registers, io: &mut dyn Io) -> Run {
+    run_traced(registers, io, &mut ())
+}
+
+/// Like `run_io`, recording every instruction that runs with `tracer`.
+pub fn run_traced(registers: &mut Registers, io: &mut dyn Io, tracer: &mut dyn Tracer) -> Run {
+    let mut state = EvalStep::new(0);
+    loop {
+        match run_step_traced(&state, registers, io, tracer) {
+            Run::Continue(next) => state = next,
+            res => return res,
+        }
+    }
+}
+
+/// Like `run_io`, checking every instruction with `watchdog` before it runs.
+pub fn run_watched(registers: &mut Registers, io: &mut dyn Io, watchdog: &mut Watchdog) -> Run {
+    let mut state = EvalStep::new(0);
+    loop {
+        if let Some(res) = watchdog.check(&state, registers) {
+            return res;
+        }
+        let mut counted = Counted { io, inputs: 0 };
+        match run_step(&state, registers, &mut counted) {
+            Run::Continue(next) => {
+                watchdog.ran(counted.inputs > 0);
+                state = next;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Evaluates and applies the single instruction at `state`.
+pub fn run_step(state: &EvalStep, registers: &mut Registers, io: &mut dyn Io) -> Run {
+    run_step_traced(state, registers, io, &mut ())
+}
+
+/// Like `run_step`, recording the instruction with `tracer` if it ran. An
+/// instruction that fails or waits for input is not recorded until it runs.
+pub fn run_step_traced(
+    state: &EvalStep,
+    registers: &mut Registers,
+    io: &mut dyn Io,
+    tracer: &mut dyn Tracer,
+) -> Run {
+    let (next, op) = match state.eval(registers) {
+        Err(e) => return Run::Error(RunError::Eval(state.position, e)),
+        Ok(decoded) => decoded,
+    };
+    let mut event = if tracer.wants(state.position, &op) {
+        Some(Event::before(state, &op, registers))
+    } else {
+        None
+    };
+    let res = match op {
+        OpCode::Stop => Run::Finished,
+        _ => match op.apply(next, registers, io) {
+            Err(OpApplicationError::NoInput) => Run::NeedsInput,
+            Err(e) => Run::Error(RunError::Apply(state.position, e)),
+            Ok(next) => Run::Continue(next),
+        },
+    };
+    if let (Some(event), Run::Finished | Run::Continue(_)) = (&mut event, &res) {
+        event.after(registers);
+        tracer.record(event);
+    }
+    res
+}
+
+#[derive(Debug)]
+pub enum Run {
+    Finished,
+    Continue(EvalStep),
+    /// An input instruction found no input; it runs again once there is some.
+    NeedsInput,
+    /// An output instruction produced this value. Only a `Machine` reports
+    /// these, the bare `run_step` hands output to its `Io` instead.
+    Output(i64),
+    /// A watchdog's budget ran out before the instruction at `position`,
+    /// after `steps` instructions.
+    Exhausted {
+        position: usize,
+        steps: u64,
+    },
+    /// The instruction at `position` was about to run from a state it had
+    /// run from before, after `steps` instructions, so the program would
+    /// never halt.
+    Looped {
+        position: usize,
+        steps: u64,
+    },
+    Error(RunError),
+}
+
+#[derive(Debug)]
+pub enum RunError {
+    Eval(usize, EvalError),
+    Apply(usize, OpApplicationError),
+}
+
+impl Error for RunError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Eval(_, e) => Some(e),
+            Self::Apply(_, e) => Some(e),
+        }
+    }
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Eval(pos, _) | Self::Apply(pos, _) => {
+                write!(f, "Error running script at position {}", pos)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OpCode {
+    Stop,
+    Add(Param, Param, Address),
+    Mul(Param, Param, Address),
+    Input(Address),
+    Output(Param),
+    JumpIfTrue(Param, Param),
+    JumpIfFalse(Param, Param),
+    LessThan(Param, Param, Address),
+    Equals(Param, Param, Address),
+    AdjustBase(Param),
+}
+
+impl OpCode {
+    /// Decodes the instruction at `position`. The cell holds the op code in
+    /// its last two digits and one mode digit per parameter above those, read
+    /// right to left, so `1002` is a multiply whose second parameter is
+    /// immediate.
+    pub fn parse(position: usize, registers: &Registers) -> Result<Self, OpCodeParseError> {
+        let code = *registers.at(position).map_err(OpCodeParseError::Register)?;
+        let modes = Modes(code);
+        let params = |count: usize| {
+            registers
+                .range(position + 1, position + count)
+                .map_err(OpCodeParseError::Register)
+        };
+
+        match code % 100 {
+            op @ 1 | op @ 2 | op @ 7 | op @ 8 => {
+                let nums = params(3)?;
+                let x = modes.read(0, nums[0])?;
+                let y = modes.read(1, nums[1])?;
+                let res = modes.write(2, nums[2])?;
+                Ok(match op {
+                    1 => Self::Add(x, y, res),
+                    2 => Self::Mul(x, y, res),
+                    7 => Self::LessThan(x, y, res),
+                    _ => Self::Equals(x, y, res),
+                })
+            }
+            3 => params(1)
+                .and_then(|nums| modes.write(0, nums[0]))
+                .map(Self::Input),
+            4 => params(1)
+                .and_then(|nums| modes.read(0, nums[0]))
+                .map(Self::Output),
+            9 => params(1)
+                .and_then(|nums| modes.read(0, nums[0]))
+                .map(Self::AdjustBase),
+            op @ 5 | op @ 6 => {
+                let nums = params(2)?;
+                let x = modes.read(0, nums[0])?;
+                let target = modes.read(1, nums[1])?;
+                if op == 5 {
+                    Ok(Self::JumpIfTrue(x, target))
+                } else {
+                    Ok(Self::JumpIfFalse(x, target))
+                }
+            }
+            99 => Ok(OpCode::Stop),
+            _ => Err(OpCodeParseError::Invalid(code)),
+        }
+    }
+
+    /// The number of cells the instruction occupies, op code included.
+    pub fn width(&self) -> usize {
+        match self {
+            Self::Stop => 1,
+            Self::Input(_) | Self::Output(_) | Self::AdjustBase(_) => 2,
+            Self::JumpIfTrue(..) | Self::JumpIfFalse(..) => 3,
+            Self::Add(..) | Self::Mul(..) | Self::LessThan(..) | Self::Equals(..) => 4,
+        }
+    }
+
+    /// The parameters the instruction reads, in order.
+    pub fn reads(&self) -> Vec<Param> {
+        match self {
+            Self::Stop | Self::Input(_) => vec![],
+            Self::Output(x) | Self::AdjustBase(x) => vec![*x],
+            Self::Add(x, y, _)
+            | Self::Mul(x, y, _)
+            | Self::LessThan(x, y, _)
+            | Self::Equals(x, y, _)
+            | Self::JumpIfTrue(x, y)
+            | Self::JumpIfFalse(x, y) => vec![*x, *y],
+        }
+    }
+
+    /// The parameter the instruction writes its result to, if any.
+    pub fn writes(&self) -> Option<Address> {
+        match self {
+            Self::Add(_, _, res)
+            | Self::Mul(_, _, res)
+            | Self::LessThan(_, _, res)
+            | Self::Equals(_, _, res)
+            | Self::Input(res) => Some(*res),
+            _ => None,
+        }
+    }
+
+    /// The cell holding the op code and parameter modes of the instruction.
+    /// Cells with leading mode digits the instruction does not use parse to
+    /// the same instruction but encode differently.
+    pub fn code(&self) -> i64 {
+        let write = self.writes().map(|address| match address {
+            Address::Position(_) => 0,
+            Address::Relative(_) => 2,
+        });
+        let reads = self.reads();
+        let modes: i64 = reads
+            .iter()
+            .map(Param::mode)
+            .chain(write)
+            .zip([100, 1_000, 10_000].iter())
+            .map(|(mode, digit)| mode * digit)
+            .sum();
+        modes + asm::opcode(self.mnemonic()).expect("Every instruction has an op code")
+    }
+
+    /// The assembly name of the instruction.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Self::Stop => "HLT",
+            Self::Add(..) => "ADD",
+            Self::Mul(..) => "MUL",
+            Self::Input(_) => "IN",
+            Self::Output(_) => "OUT",
+            Self::JumpIfTrue(..) => "JT",
+            Self::JumpIfFalse(..) => "JF",
+            Self::LessThan(..) => "LT",
+            Self::Equals(..) => "EQ",
+            Self::AdjustBase(_) => "ARB",
+        }
+    }
+
+    /// Executes the instruction given the step straight after it, returning
+    /// the step to continue from: `next` itself unless the instruction jumps
+    /// or adjusts the relative base.
+    pub fn apply(
+        &self,
+        next: EvalStep,
+        registers: &mut Registers,
+        io: &mut dyn Io,
+    ) -> Result<EvalStep, OpApplicationError> {
+        let base = next.relative_base;
+        let read = |param: &Param| {
+            param
+                .read(registers, base)
+                .map_err(OpApplicationError::Register)
+        };
+        let jump = |target: &Param| {
+            let target = read(target)?;
+            usize::try_from(target)
+                .map(|position| EvalStep { position, ..next })
+                .map_err(|_| OpApplicationError::NegativeAddress(target))
+        };
+
+        let (resloc, value) = match self {
+            Self::Stop => return Err(OpApplicationError::Stop),
+            Self::Add(x, y, resloc) => {
+                let (x, y) = (read(x)?, read(y)?);
+                let sum = x
+                    .checked_add(y)
+                    .ok_or(OpApplicationError::Overflow("+", x, y))?;
+                (resloc, sum)
+            }
+            Self::Mul(x, y, resloc) => {
+                let (x, y) = (read(x)?, read(y)?);
+                let product = x
+                    .checked_mul(y)
+                    .ok_or(OpApplicationError::Overflow("*", x, y))?;
+                (resloc, product)
+            }
+            Self::LessThan(x, y, resloc) => (resloc, (read(x)? < read(y)?) as i64),
+            Self::Equals(x, y, resloc) => (resloc, (read(x)? == read(y)?) as i64),
+            Self::Input(resloc) => (resloc, io.input().ok_or(OpApplicationError::NoInput)?),
+            Self::Output(x) => {
+                io.output(read(x)?);
+                return Ok(next);
+            }
+            Self::JumpIfTrue(x, target) => {
+                return if read(x)? != 0 {
+                    jump(target)
+                } else {
+                    Ok(next)
+                };
+            }
+            Self::JumpIfFalse(x, target) => {
+                return if read(x)? == 0 {
+                    jump(target)
+                } else {
+                    Ok(next)
+                };
+            }
+            Self::AdjustBase(x) => {
+                let x = read(x)?;
+                let relative_base = base
+                    .checked_add(x)
+                    .ok_or(OpApplicationError::Overflow("+", base, x))?;
+                return Ok(EvalStep {
+                    relative_base,
+                    ..next
+                });
+            }
+        };
+
+        resloc
+            .resolve(base)
+            .and_then(|address| registers.set(address, value))
+            .map(|_| next)
+            .map_err(OpApplicationError::Register)
+    }
+}
+
+/// A parameter an instruction reads from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Param {
+    /// Mode 0: the value is at this address.
+    Position(usize),
+    /// Mode 1: the value is the parameter itself.
+    Immediate(i64),
+    /// Mode 2: the value is at this offset from the relative base.
+    Relative(i64),
+}
+
+impl Param {
+    fn mode(&self) -> i64 {
+        match self {
+            Self::Position(_) => 0,
+            Self::Immediate(_) => 1,
+            Self::Relative(_) => 2,
+        }
+    }
+
+    pub fn read(&self, registers: &Registers, relative_base: i64) -> RegisterResult<i64> {
+        match self {
+            Self::Position(address) => registers.at(*address).copied(),
+            Self::Immediate(value) => Ok(*value),
+            Self::Relative(offset) => Address::Relative(*offset)
+                .resolve(relative_base)
+                .and_then(|address| registers.at(address).copied()),
+        }
+    }
+}
+
+/// A parameter an instruction writes to, which can never be immediate.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Address {
+    Position(usize),
+    Relative(i64),
+}
+
+impl Address {
+    pub fn resolve(&self, relative_base: i64) -> RegisterResult<usize> {
+        match self {
+            Self::Position(address) => Ok(*address),
+            Self::Relative(offset) => {
+                let address = relative_base.saturating_add(*offset);
+                usize::try_from(address).map_err(|_| RegisterErr::Negative(address))
+            }
+        }
+    }
+}
+
+/// Instructions display as assembly, e.g. `MUL [4], #3, rb+2`.
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        match self {
+            Self::Stop => Ok(()),
+            Self::Add(x, y, res)
+            | Self::Mul(x, y, res)
+            | Self::LessThan(x, y, res)
+            | Self::Equals(x, y, res) => write!(f, " {}, {}, {}", x, y, res),
+            Self::Input(res) => write!(f, " {}", res),
+            Self::Output(x) | Self::AdjustBase(x) => write!(f, " {}", x),
+            Self::JumpIfTrue(x, target) | Self::JumpIfFalse(x, target) => {
+                write!(f, " {}, {}", x, target)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Position(address) => write!(f, "[{}]", address),
+            Self::Immediate(value) => write!(f, "#{}", value),
+            Self::Relative(offset) => write!(f, "rb{:+}", offset),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Position(address) => write!(f, "[{}]", address),
+            Self::Relative(offset) => write!(f, "rb{:+}", offset),
+        }
+    }
+}
+
+/// The mode digits of an instruction cell.
+struct Modes(i64);
+
+impl Modes {
+    fn mode(&self, param: u32) -> i64 {
+        self.0 / 10i64.pow(param + 2) % 10
+    }
+
+    fn read(&self, param: u32, value: i64) -> Result<Param, OpCodeParseError> {
+        match self.mode(param) {
+            0 => usize::try_from(value)
+                .map(Param::Position)
+                .map_err(|_| OpCodeParseError::NegativeAddress(param, value)),
+            1 => Ok(Param::Immediate(value)),
+            2 => Ok(Param::Relative(value)),
+            _ => Err(OpCodeParseError::InvalidMode(self.0, param)),
+        }
+    }
+
+    /// Parameters that are written to are always addresses.
+    fn write(&self, param: u32, value: i64) -> Result<Address, OpCodeParseError> {
+        match self.read(param, value)? {
+            Param::Position(address) => Ok(Address::Position(address)),
+            Param::Relative(offset) => Ok(Address::Relative(offset)),
+            Param::Immediate(_) => Err(OpCodeParseError::ImmediateWrite(self.0, param)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OpCodeParseError {
+    Register(RegisterErr),
+    Invalid(i64),
+    /// An unknown mode digit for the given parameter of the op code.
+    InvalidMode(i64, u32),
+    /// An immediate mode digit for a parameter the op code writes to.
+    ImmediateWrite(i64, u32),
+    /// A position mode parameter holding a negative address.
+    NegativeAddress(u32, i64),
+}
+
+impl Error for OpCodeParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Register(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for OpCodeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Register(_) => write!(f, "Failure parsing OpCode"),
+            Self::Invalid(code) => write!(f, "Invalid op code {}", code),
+            Self::InvalidMode(code, param) => write!(
+                f,
+                "Invalid mode for parameter {} of op code {}",
+                param + 1,
+                code
+            ),
+            Self::NegativeAddress(param, address) => write!(
+                f,
+                "Parameter {} refers to negative address {}",
+                param + 1,
+                address
+            ),
+            Self::ImmediateWrite(code, param) => write!(
+                f,
+                "Parameter {} of op code {} is written to and cannot be immediate",
+                param + 1,
+                code
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OpApplicationError {
+    Stop,
+    Register(RegisterErr),
+    NoInput,
+    /// The operator and operands of arithmetic that does not fit in a cell.
+    Overflow(&'static str, i64, i64),
+    NegativeAddress(i64),
+}
+
+impl Error for OpApplicationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Register(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for OpApplicationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Stop => write!(f, "Attempted to apply Stop op code"),
+            Self::Register(_) => write!(f, "Math failed"),
+            Self::NoInput => write!(f, "No input available"),
+            Self::Overflow(op, x, y) => write!(f, "{} {} {} overflows", x, op, y),
+            Self::NegativeAddress(a) => write!(f, "Cannot jump to negative address {}", a),
+        }
+    }
+}
+
+/// The devices an Intcode program reads its input from and writes its output
+/// to.
+pub trait Io {
+    /// The next input value, or `None` if there is nothing left to read.
+    fn input(&mut self) -> Option<i64>;
+
+    fn output(&mut self, value: i64);
+}
+
+/// No devices at all: there is never any input and output is discarded.
+impl Io for () {
+    fn input(&mut self) -> Option<i64> {
+        None
+    }
+
+    fn output(&mut self, _value: i64) {}
+}
+
+/// Inputs taken in order from a queue, with outputs collected alongside.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Queue {
+    pub input: VecDeque<i64>,
+    pub output: Vec<i64>,
+}
+
+impl Queue {
+    pub fn new<I: IntoIterator<Item = i64>>(input: I) -> Self {
+        Self {
+            input: input.into_iter().collect(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl Io for Queue {
+    fn input(&mut self) -> Option<i64> {
+        self.input.pop_front()
+    }
+
+    fn output(&mut self, value: i64) {
+        self.output.push(value);
+    }
+}
+
+/// Inputs read from stdin one per line, with outputs printed to stdout.
+pub struct Stdio;
+
+impl Io for Stdio {
+    fn input(&mut self) -> Option<i64> {
+        for line in io::stdin().lock().lines() {
+            let line = line.ok()?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            match line.parse() {
+                Ok(value) => return Some(value),
+                Err(_) => eprintln!("{:?} is not an Intcode value", line),
+            }
+        }
+        None
+    }
+
+    fn output(&mut self, value: i64) {
+        println!("{}", value);
+    }
+}
+
+/// Inputs produced by one closure and outputs handed to another.
+pub struct Callbacks<I, O> {
+    pub input: I,
+    pub output: O,
+}
+
+impl<I, O> Io for Callbacks<I, O>
+where
+    I: FnMut() -> Option<i64>,
+    O: FnMut(i64),
+{
+    fn input(&mut self) -> Option<i64> {
+        (self.input)()
+    }
+
+    fn output(&mut self, value: i64) {
+        (self.output)(value)
+    }
+}
+
+/// Addresses at or beyond this are errors unless another limit is given with
+/// `Registers::with_limit`.
+pub const DEFAULT_LIMIT: usize = 1 << 30;
+
+/// Writes this far past the end of dense memory grow it, anything further
+/// out is kept sparse.
+const DENSE_GROWTH: usize = 4096;
+
+/// The memory of an Intcode machine. Every address below the limit can be
+/// read, with cells that were never written holding zero. The cells from the
+/// program onwards are stored densely and writes far beyond them sparsely,
+/// so huge addresses stay cheap.
+#[derive(Debug, Clone)]
+pub struct Registers {
+    dense: Vec<i64>,
+    sparse: BTreeMap<usize, i64>,
+    limit: usize,
+    fingerprint: u64,
+}
+
+impl Registers {
+    pub fn new(cells: Vec<i64>) -> Self {
+        let fingerprint = cells.iter().enumerate().fold(0, |sum: u64, (num, val)| {
+            sum.wrapping_add(cell_hash(num, *val))
+        });
+        Self {
+            dense: cells,
+            sparse: BTreeMap::new(),
+            limit: DEFAULT_LIMIT,
+            fingerprint,
+        }
+    }
+
+    /// Parses comma separated Intcode, which may span several lines.
+    pub fn from_program(program: &str) -> Result<Self, error::Error> {
+        Self::from_input(&input::Input::new("<program>", program))
+    }
+
+    pub fn from_input(input: &input::Input) -> Result<Self, error::Error> {
+        input.csv().map(Self::new)
+    }
+
+    /// Restricts addresses to those below `limit`.
+    pub fn with_limit(self, limit: usize) -> Self {
+        Self { limit, ..self }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// The number of densely stored cells: the program and anything written
+    /// shortly after it.
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    /// A hash of the whole memory, kept up to date on every write so that
+    /// memories can be told apart cheaply. Equal memories always share a
+    /// fingerprint, and different ones almost never do.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub fn at(&self, num: usize) -> RegisterResult<&i64> {
+        if num >= self.limit {
+            return Err(RegisterErr::Missing(num));
+        }
+        Ok(self
+            .dense
+            .get(num)
+            .or_else(|| self.sparse.get(&num))
+            .unwrap_or(&0))
+    }
+
+    pub fn set(&mut self, num: usize, val: i64) -> RegisterResult<()> {
+        if num >= self.limit {
+            return Err(RegisterErr::Insert(num));
+        }
+        let old = *self.at(num)?;
+        self.fingerprint = self
+            .fingerprint
+            .wrapping_sub(cell_hash(num, old))
+            .wrapping_add(cell_hash(num, val));
+        if num < self.dense.len() {
+            self.dense[num] = val;
+        } else if num - self.dense.len() < DENSE_GROWTH {
+            self.grow(num + 1);
+            self.dense[num] = val;
+        } else {
+            self.sparse.insert(num, val);
+        }
+        Ok(())
+    }
+
+    /// Extends dense memory to `len` cells, taking over any sparse cells it
+    /// now covers.
+    fn grow(&mut self, len: usize) {
+        let rest = self.sparse.split_off(&len);
+        self.dense.resize(len, 0);
+        for (num, val) in mem::replace(&mut self.sparse, rest) {
+            self.dense[num] = val;
+        }
+    }
+
+    pub fn range(&self, start: usize, end: usize) -> RegisterResult<Vec<i64>> {
+        if end >= self.limit {
+            return Err(RegisterErr::MissingRange(start, end));
+        }
+        (start..=end).map(|num| self.at(num).copied()).collect()
+    }
+}
+
+/// One cell's share of a memory fingerprint. Zero cells have none, so
+/// unwritten memory makes no difference however it is stored.
+fn cell_hash(num: usize, val: i64) -> u64 {
+    if val == 0 {
+        return 0;
+    }
+    // The SplitMix64 finalizer, mixing the address and value together.
+    let mut x = (num as u64).rotate_left(32) ^ (val as u64);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+impl From<Vec<i64>> for Registers {
+    fn from(cells: Vec<i64>) -> Self {
+        Self::new(cells)
+    }
+}
+
+pub type RegisterResult<T> = Result<T, RegisterErr>;
+
+#[derive(Debug)]
+pub enum RegisterErr {
+    Insert(usize),
+    Missing(usize),
+    MissingRange(usize, usize),
+    Negative(i64),
+}
+
+impl Error for RegisterErr {}
+impl fmt::Display for RegisterErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Insert(p) => write!(f, "Failed to insert at position {} beyond the limit", p),
+            Self::Missing(p) => write!(f, "No value at position {} beyond the limit", p),
+            Self::MissingRange(s, e) => {
+                write!(f, "Range [{}, {}] reaches beyond the limit", s, e)
+            }
+            Self::Negative(p) => write!(f, "Negative address {}", p),
+        }
+    }
+}
+
+/// The instruction pointer and relative base of a running machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalStep {
+    pub position: usize,
+    pub relative_base: i64,
+}
+
+impl EvalStep {
+    pub fn new(position: usize) -> Self {
+        Self {
+            position,
+            relative_base: 0,
+        }
+    }
+
+    /// Decodes the instruction at this position, along with the step for the
+    /// instruction straight after it.
+    pub fn eval(&self, registers: &Registers) -> EvalResult {
+        OpCode::parse(self.position, registers)
+            .map_err(EvalError)
+            .map(|op| {
+                let next = Self {
+                    position: self.position + op.width(),
+                    ..*self
+                };
+                (next, op)
+            })
+    }
+}
+
+pub type EvalResult = Result<(EvalStep, OpCode), EvalError>;
+
+#[derive(Debug)]
+pub struct EvalError(OpCodeParseError);
+
+impl Error for EvalError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Error in evaluation")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assemble(source: &str) -> Machine {
+        Machine::new(Registers::from(
+            asm::assemble(source).expect("Failed to assemble program"),
+        ))
+    }
+
+    #[test]
+    fn test_machine() {
+        let mut machine = assemble(
+            "
+            x:  IN [x]
+                OUT [x]
+                ADD [x], #1, [x]
+                OUT [x]
+                HLT
+            ",
+        );
+        machine.feed_all(vec![41]);
+        assert!(matches!(machine.run(), Run::Finished));
+        assert_eq!(vec![41, 42], machine.drain_output());
+        assert!(machine.drain_output().is_empty());
+        assert_eq!(Some(42), machine.peek(0).ok());
+        assert_eq!(10, machine.state().position);
+
+        let mut machine = assemble("ADD [x], [y], [0]\nHLT\nx: DATA 0\ny: DATA 0");
+        machine.poke(5, 30).expect("Failed to poke");
+        machine.poke(6, 12).expect("Failed to poke");
+        assert!(matches!(
+            machine.step(),
+            Run::Continue(EvalStep { position: 4, .. })
+        ));
+        assert_eq!(42, *machine.registers().at(0).unwrap());
+        assert!(matches!(machine.step(), Run::Finished));
+        assert_eq!(4, machine.state().position);
+
+        let mut machine = Machine::from_program("3,0,99").expect("Failed to parse program");
+        assert!(matches!(machine.run(), Run::NeedsInput));
+        assert_eq!(0, machine.state().position);
+        machine.feed(5);
+        assert!(matches!(machine.run(), Run::Finished));
+        assert_eq!(Some(5), machine.peek(0).ok());
+    }
+
+    #[test]
+    fn test_resume() {
+        let program = assemble(
+            "
+                    IN [x]
+                    OUT [x]
+                    ADD [x], #1, [x]
+                    OUT [x]
+                    HLT
+            x:      DATA 0
+            ",
+        );
+
+        let mut machine = program.clone();
+        assert!(matches!(machine.resume(), Run::NeedsInput));
+        machine.feed(5);
+        assert!(matches!(machine.resume(), Run::Output(5)));
+        assert!(matches!(machine.resume(), Run::Output(6)));
+        assert!(matches!(machine.resume(), Run::Finished));
+        assert!(machine.drain_output().is_empty());
+
+        let mut machine = program;
+        machine.feed(5);
+        assert!(matches!(machine.step(), Run::Continue(_)));
+        assert!(matches!(machine.step(), Run::Output(5)));
+        assert!(matches!(machine.run(), Run::Finished));
+        assert_eq!(vec![6], machine.drain_output());
+
+        let mut countdown = assemble(
+            "
+                    IN [n]
+            loop:   OUT [n]
+                    ADD [n], #-1, [n]
+                    JT [n], #loop
+                    HLT
+            n:      DATA 0
+            ",
+        );
+        countdown.feed(3);
+        let outputs: Vec<_> = std::iter::from_fn(|| match countdown.resume() {
+            Run::Output(value) => Some(value),
+            _ => None,
+        })
+        .collect();
+        assert_eq!(vec![3, 2, 1], outputs);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut registers = Registers::from(vec![1, 0, 0, 0, 99]);
+        run_script(&mut registers);
+        assert_eq!(
+            [2, 0, 0, 0, 99],
+            *registers.range(0, 4).expect("test 1 failed")
+        );
+
+        let mut registers = Registers::from(vec![2, 3, 0, 3, 99]);
+        run_script(&mut registers);
+        assert_eq!(
+            [2, 3, 0, 6, 99],
+            *registers.range(0, 4).expect("test 2 failed")
+        );
+
+        let mut registers = Registers::from(vec![2, 4, 4, 5, 99, 0]);
+        run_script(&mut registers);
+        assert_eq!(
+            [2, 4, 4, 5, 99, 9801],
+            *registers.range(0, 5).expect("test 3 failed")
+        );
+
+        let mut registers = Registers::from(vec![1, 1, 1, 4, 99, 5, 6, 0, 99]);
+        run_script(&mut registers);
+        assert_eq!(
+            [30, 1, 1, 4, 2, 5, 6, 0, 99],
+            *registers.range(0, 8).expect("test 4 failed")
+        );
+
+        let mut registers = Registers::from(vec![1002, 4, 3, 4, 33]);
+        run_script(&mut registers);
+        assert_eq!(
+            [1002, 4, 3, 4, 99],
+            *registers.range(0, 4).expect("test 5 failed")
+        );
+    }
+
+    #[test]
+    fn test_run_io() {
+        let program = Registers::from(vec![3, 0, 4, 0, 99]);
+
+        let mut registers = program.clone();
+        let mut queue = Queue::new(vec![42]);
+        assert!(matches!(run_io(&mut registers, &mut queue), Run::Finished));
+        assert_eq!(vec![42], queue.output);
+        assert!(queue.input.is_empty());
+
+        let mut registers = program.clone();
+        let mut outputs = Vec::new();
+        let mut callbacks = Callbacks {
+            input: || Some(7),
+            output: |value| outputs.push(value),
+        };
+        assert!(matches!(
+            run_io(&mut registers, &mut callbacks),
+            Run::Finished
+        ));
+        assert_eq!(vec![7], outputs);
+
+        let mut registers = program;
+        assert!(matches!(run_script(&mut registers), Run::NeedsInput));
+    }
+
+    #[test]
+    fn test_jumps_and_comparisons() {
+        let run = |program: &[i64], input: i64| {
+            let mut registers = Registers::from(program.to_vec());
+            let mut queue = Queue::new(vec![input]);
+            assert!(matches!(run_io(&mut registers, &mut queue), Run::Finished));
+            queue.output
+        };
+
+        let equal_to_eight = [3, 9, 8, 9, 10, 9, 4, 9, 99, 0, 8];
+        assert_eq!(vec![1], run(&equal_to_eight, 8));
+        assert_eq!(vec![0], run(&equal_to_eight, 7));
+
+        let less_than_eight = [3, 3, 1107, 0, 8, 3, 4, 3, 99];
+        assert_eq!(vec![1], run(&less_than_eight, 7));
+        assert_eq!(vec![0], run(&less_than_eight, 9));
+
+        let non_zero = [3, 3, 1105, 0, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+        assert_eq!(vec![0], run(&non_zero, 0));
+        assert_eq!(vec![1], run(&non_zero, 5));
+
+        let compare_to_eight = [
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        assert_eq!(vec![999], run(&compare_to_eight, 7));
+        assert_eq!(vec![1000], run(&compare_to_eight, 8));
+        assert_eq!(vec![1001], run(&compare_to_eight, 9));
+    }
+
+    #[test]
+    fn test_signed_values() {
+        let mut registers =
+            Registers::from_program("1101,100,-1,4,0").expect("Failed to parse negative literal");
+        assert!(matches!(run_script(&mut registers), Run::Finished));
+        assert_eq!(Some(&99), registers.at(4).ok());
+
+        let mut registers = Registers::from(vec![1101, i64::MAX, 1, 0, 99]);
+        match run_script(&mut registers) {
+            Run::Error(RunError::Apply(0, e @ OpApplicationError::Overflow(..))) => {
+                assert_eq!("9223372036854775807 + 1 overflows", e.to_string())
+            }
+            _ => panic!("Expected an overflow at position 0"),
+        }
+
+        let mut registers = Registers::from(vec![1, -1, 0, 0, 99]);
+        assert!(matches!(
+            run_script(&mut registers),
+            Run::Error(RunError::Eval(0, _))
+        ));
+
+        let mut registers = Registers::from(vec![1105, 1, -4, 99]);
+        assert!(matches!(
+            run_script(&mut registers),
+            Run::Error(RunError::Apply(0, OpApplicationError::NegativeAddress(-4)))
+        ));
+    }
+
+    #[test]
+    fn test_relative_mode() {
+        let run = |program: &str| {
+            let mut registers = Registers::from_program(program).expect("Failed to parse program");
+            let mut queue = Queue::default();
+            assert!(matches!(run_io(&mut registers, &mut queue), Run::Finished));
+            queue.output
+        };
+
+        let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let expectation: Vec<i64> = quine.split(',').map(|n| n.parse().unwrap()).collect();
+        assert_eq!(expectation, run(quine));
+        assert_eq!(
+            vec![1_219_070_632_396_864],
+            run("1102,34915192,34915192,7,4,7,99,0")
+        );
+        assert_eq!(vec![1_125_899_906_842_624], run("104,1125899906842624,99"));
+
+        let mut registers = Registers::from(vec![109, -1, 204, 0, 99]);
+        assert!(matches!(
+            run_script(&mut registers),
+            Run::Error(RunError::Apply(
+                2,
+                OpApplicationError::Register(RegisterErr::Negative(-1))
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut registers = Registers::from(vec![1, 2, 3]).with_limit(1 << 20);
+        assert_eq!(Some(&0), registers.at(100).ok());
+
+        registers.set(10, 7).expect("Failed to grow memory");
+        assert_eq!([0, 0, 7], *registers.range(8, 10).unwrap());
+        assert_eq!(11, registers.dense.len());
+
+        registers
+            .set(500_000, 9)
+            .expect("Failed to write sparse memory");
+        registers
+            .set(5_000, 8)
+            .expect("Failed to write sparse memory");
+        assert_eq!(Some(&9), registers.at(500_000).ok());
+        assert_eq!(11, registers.dense.len());
+
+        registers.set(4_000, 1).expect("Failed to grow memory");
+        registers.set(8_000, 2).expect("Failed to grow memory");
+        assert_eq!(8_001, registers.dense.len());
+        assert_eq!(Some(&8), registers.at(5_000).ok());
+        assert_eq!(
+            vec![500_000],
+            registers.sparse.keys().copied().collect::<Vec<_>>()
+        );
+
+        assert!(matches!(
+            registers.set(1 << 20, 1),
+            Err(RegisterErr::Insert(_))
+        ));
+        assert!(matches!(
+            registers.at(1 << 20),
+            Err(RegisterErr::Missing(_))
+        ));
+
+        // The fingerprint follows the contents, not how they are stored.
+        let mut registers = Registers::from(vec![1, 2, 3]);
+        let fingerprint = registers.fingerprint();
+        registers.set(1, 5).expect("Failed to write memory");
+        assert_ne!(fingerprint, registers.fingerprint());
+        registers
+            .set(9_000, 4)
+            .expect("Failed to write sparse memory");
+        registers.set(1, 2).expect("Failed to write memory");
+        registers
+            .set(9_000, 0)
+            .expect("Failed to write sparse memory");
+        assert_eq!(fingerprint, registers.fingerprint());
+        assert_eq!(
+            Registers::from(vec![1, 2, 3, 0, 0]).fingerprint(),
+            fingerprint
+        );
+    }
+
+    #[test]
+    fn test_op_parse() {
+        let registers = Registers::from(vec![1, 0, 0, 0, 99]);
+        let code = OpCode::parse(0, &registers).expect("Failed to parse add op code");
+        assert_eq!(
+            OpCode::Add(Param::Position(0), Param::Position(0), Address::Position(0)),
+            code
+        );
+
+        let registers = Registers::from(vec![2, 0, 0, 0, 99]);
+        let code = OpCode::parse(0, &registers).expect("Failed to parse mul op code");
+        assert_eq!(
+            OpCode::Mul(Param::Position(0), Param::Position(0), Address::Position(0)),
+            code
+        );
+
+        let registers = Registers::from(vec![99, 0, 0, 0, 99]);
+        let code = OpCode::parse(0, &registers).expect("Failed to parse stop op code");
+        assert_eq!(OpCode::Stop, code);
+
+        let registers = Registers::from(vec![1002, 4, 3, 4, 33]);
+        let code = OpCode::parse(0, &registers).expect("Failed to parse moded op code");
+        assert_eq!(
+            OpCode::Mul(
+                Param::Position(4),
+                Param::Immediate(3),
+                Address::Position(4)
+            ),
+            code
+        );
+
+        let registers = Registers::from(vec![104, 7, 3, 9, 99]);
+        let code = OpCode::parse(0, &registers).expect("Failed to parse output op code");
+        assert_eq!(OpCode::Output(Param::Immediate(7)), code);
+        let code = OpCode::parse(2, &registers).expect("Failed to parse input op code");
+        assert_eq!(OpCode::Input(Address::Position(9)), code);
+
+        let registers = Registers::from(vec![1005, 0, 7, 99]);
+        let code = OpCode::parse(0, &registers).expect("Failed to parse jump op code");
+        assert_eq!(
+            OpCode::JumpIfTrue(Param::Position(0), Param::Immediate(7)),
+            code
+        );
+        assert_eq!(3, code.width());
+
+        let registers = Registers::from(vec![10001, 4, 3, 4, 33]);
+        match OpCode::parse(0, &registers) {
+            Err(OpCodeParseError::ImmediateWrite(10001, 2)) => {}
+            other => panic!("Expected an immediate write error, got {:?}", other),
+        }
+
+        let registers = Registers::from(vec![301, 4, 3, 4, 33]);
+        match OpCode::parse(0, &registers) {
+            Err(OpCodeParseError::InvalidMode(301, 0)) => {}
+            other => panic!("Expected an invalid mode error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_op_apply() {
+        let mut registers = Registers::from(vec![1, 0, 0, 0, 99]);
+        let code = OpCode::Add(Param::Position(0), Param::Position(1), Address::Position(3));
+        code.apply(EvalStep::new(4), &mut registers, &mut ())
+            .expect("Addition application failed");
+        assert_eq!([1, 0, 0, 1, 99], *registers.range(0, 4).unwrap());
+
+        let mut registers = Registers::from(vec![1, 3, 0, 0, 99]);
+        let code = OpCode::Mul(Param::Position(0), Param::Position(1), Address::Position(3));
+        code.apply(EvalStep::new(4), &mut registers, &mut ())
+            .expect("Multiplication application failed");
+        assert_eq!([1, 3, 0, 3, 99], *registers.range(0, 4).unwrap());
+
+        let mut registers = Registers::from(vec![109, 5, 99]);
+        let next = OpCode::AdjustBase(Param::Immediate(-3))
+            .apply(EvalStep::new(2), &mut registers, &mut ())
+            .expect("Relative base adjustment failed");
+        assert_eq!(-3, next.relative_base);
+        OpCode::Add(Param::Relative(4), Param::Relative(5), Address::Relative(3))
+            .apply(next, &mut registers, &mut ())
+            .expect("Relative addition failed");
+        assert_eq!([104, 5, 99], *registers.range(0, 2).unwrap());
+    }
+}
@@ -0,0 +1,439 @@
+//! Assembles Intcode from the syntax the disassembler lists it in, so that
+//! test programs can be written readably.
+//!
+//! Each line holds an instruction or a `DATA` directive, optionally preceded
+//! by labels and followed by a `;` comment:
+//!
+//! ```text
+//!         IN [n]
+//! loop:   OUT [n]
+//!         ADD [n], #-1, [n]
+//!         JT [n], #loop
+//!         HLT
+//! n:      DATA 0
+//! ```
+//!
+//! Operands are `[address]` for position mode, `#value` for immediate mode
+//! and `rb+offset` for relative mode. Addresses and values may be numbers,
+//! labels or a label plus or minus a number. A numeric label such as `12:`
+//! asserts the address of its line, which lets disassembler output be fed
+//! straight back in.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// The op code and the number of operands of each mnemonic, and which
+/// operand, if any, is written to.
+const MNEMONICS: &[(&str, i64, usize, Option<usize>)] = &[
+    ("ADD", 1, 3, Some(2)),
+    ("MUL", 2, 3, Some(2)),
+    ("IN", 3, 1, Some(0)),
+    ("OUT", 4, 1, None),
+    ("JT", 5, 2, None),
+    ("JF", 6, 2, None),
+    ("LT", 7, 3, Some(2)),
+    ("EQ", 8, 3, Some(2)),
+    ("ARB", 9, 1, None),
+    ("HLT", 99, 0, None),
+];
+
+/// The op code `mnemonic` assembles to, ignoring case.
+pub fn opcode(mnemonic: &str) -> Option<i64> {
+    let mnemonic = mnemonic.to_ascii_uppercase();
+    MNEMONICS
+        .iter()
+        .find(|(name, ..)| *name == mnemonic)
+        .map(|(_, code, ..)| *code)
+}
+
+/// Assembles `source` into Intcode.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0;
+
+    for (number, line) in source.lines().enumerate() {
+        let at = |kind| AsmError {
+            line: number + 1,
+            kind,
+        };
+        let mut rest = line.split(';').next().unwrap_or_default();
+        while let Some((label, after)) = rest.split_once(':') {
+            let label = label.trim();
+            match label.parse::<usize>() {
+                Ok(expected) if expected != address => {
+                    return Err(at(AsmErrorKind::Misplaced { expected, address }))
+                }
+                Ok(_) => {}
+                Err(_) if is_label(label) => {
+                    if labels.insert(label.to_owned(), address).is_some() {
+                        return Err(at(AsmErrorKind::DuplicateLabel(label.to_owned())));
+                    }
+                }
+                Err(_) => return Err(at(AsmErrorKind::BadLabel(label.to_owned()))),
+            }
+            rest = after;
+        }
+        let statement = Statement::parse(rest).map_err(at)?;
+        address += statement.width();
+        statements.push((number + 1, statement));
+    }
+
+    let mut program = Vec::with_capacity(address);
+    for (line, statement) in statements {
+        statement
+            .encode(&labels, &mut program)
+            .map_err(|kind| AsmError { line, kind })?;
+    }
+    Ok(program)
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// What a line assembles to.
+enum Statement {
+    Empty,
+    Data(Vec<Value>),
+    /// An op code with its operands.
+    Instruction(i64, Vec<Operand>),
+}
+
+impl Statement {
+    fn parse(text: &str) -> Result<Self, AsmErrorKind> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(Self::Empty);
+        }
+        let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let operands: Vec<&str> = operands
+            .split(',')
+            .map(str::trim)
+            .filter(|o| !o.is_empty())
+            .collect();
+        let mnemonic = mnemonic.to_ascii_uppercase();
+
+        if mnemonic == "DATA" {
+            return operands
+                .iter()
+                .map(|o| Value::parse(o))
+                .collect::<Result<_, _>>()
+                .map(Self::Data);
+        }
+        let (_, code, count, write) = MNEMONICS
+            .iter()
+            .find(|(name, ..)| *name == mnemonic)
+            .ok_or(AsmErrorKind::UnknownMnemonic(mnemonic.clone()))?;
+        if operands.len() != *count {
+            return Err(AsmErrorKind::Operands {
+                mnemonic,
+                expected: *count,
+                found: operands.len(),
+            });
+        }
+        let operands = operands
+            .iter()
+            .map(|o| Operand::parse(o))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(Operand::Immediate(_)) = write.and_then(|w| operands.get(w)) {
+            return Err(AsmErrorKind::ImmediateWrite(mnemonic));
+        }
+        Ok(Self::Instruction(*code, operands))
+    }
+
+    /// The number of cells the statement assembles to.
+    fn width(&self) -> usize {
+        match self {
+            Self::Empty => 0,
+            Self::Data(values) => values.len(),
+            Self::Instruction(_, operands) => 1 + operands.len(),
+        }
+    }
+
+    fn encode(
+        &self,
+        labels: &HashMap<String, usize>,
+        program: &mut Vec<i64>,
+    ) -> Result<(), AsmErrorKind> {
+        match self {
+            Self::Empty => {}
+            Self::Data(values) => {
+                for value in values {
+                    program.push(value.resolve(labels)?);
+                }
+            }
+            Self::Instruction(code, operands) => {
+                let modes: i64 = operands
+                    .iter()
+                    .zip([100, 1_000, 10_000].iter())
+                    .map(|(operand, digit)| operand.mode() * digit)
+                    .sum();
+                program.push(modes + code);
+                for operand in operands {
+                    program.push(operand.resolve(labels)?);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Operand {
+    Position(Value),
+    Immediate(Value),
+    Relative(i64),
+}
+
+impl Operand {
+    fn parse(text: &str) -> Result<Self, AsmErrorKind> {
+        let bad = || AsmErrorKind::BadOperand(text.to_owned());
+        if let Some(value) = text.strip_prefix('#') {
+            Value::parse(value).map(Self::Immediate)
+        } else if let Some(address) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            Value::parse(address).map(Self::Position)
+        } else if let Some(offset) = text.strip_prefix("rb") {
+            match offset.trim() {
+                "" => Ok(Self::Relative(0)),
+                offset => offset
+                    .strip_prefix('+')
+                    .unwrap_or(offset)
+                    .trim()
+                    .parse()
+                    .map(Self::Relative)
+                    .map_err(|_| bad()),
+            }
+        } else {
+            Err(bad())
+        }
+    }
+
+    fn mode(&self) -> i64 {
+        match self {
+            Self::Position(_) => 0,
+            Self::Immediate(_) => 1,
+            Self::Relative(_) => 2,
+        }
+    }
+
+    fn resolve(&self, labels: &HashMap<String, usize>) -> Result<i64, AsmErrorKind> {
+        match self {
+            Self::Position(value) => match value.resolve(labels)? {
+                address if address < 0 => Err(AsmErrorKind::NegativeAddress(address)),
+                address => Ok(address),
+            },
+            Self::Immediate(value) => value.resolve(labels),
+            Self::Relative(offset) => Ok(*offset),
+        }
+    }
+}
+
+/// A number, or a label's address with an optional offset.
+enum Value {
+    Number(i64),
+    Label(String, i64),
+}
+
+impl Value {
+    fn parse(text: &str) -> Result<Self, AsmErrorKind> {
+        let text = text.trim();
+        if let Ok(number) = text.parse() {
+            return Ok(Self::Number(number));
+        }
+        let (label, offset) = match text.find(['+', '-']) {
+            Some(i) => {
+                let offset = text[i + 1..].trim().parse::<i64>();
+                let offset = offset.map_err(|_| AsmErrorKind::BadOperand(text.to_owned()))?;
+                let sign = if text[i..].starts_with('-') { -1 } else { 1 };
+                (text[..i].trim(), sign * offset)
+            }
+            None => (text, 0),
+        };
+        if is_label(label) {
+            Ok(Self::Label(label.to_owned(), offset))
+        } else {
+            Err(AsmErrorKind::BadOperand(text.to_owned()))
+        }
+    }
+
+    fn resolve(&self, labels: &HashMap<String, usize>) -> Result<i64, AsmErrorKind> {
+        match self {
+            Self::Number(number) => Ok(*number),
+            Self::Label(label, offset) => labels
+                .get(label)
+                .ok_or_else(|| AsmErrorKind::UnknownLabel(label.clone()))
+                .and_then(|address| {
+                    (*address as i64)
+                        .checked_add(*offset)
+                        .ok_or_else(|| AsmErrorKind::Overflow(format!("{}{:+}", label, offset)))
+                }),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub kind: AsmErrorKind,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    UnknownMnemonic(String),
+    Operands {
+        mnemonic: String,
+        expected: usize,
+        found: usize,
+    },
+    BadOperand(String),
+    ImmediateWrite(String),
+    BadLabel(String),
+    DuplicateLabel(String),
+    UnknownLabel(String),
+    /// A label plus an offset that does not fit in an Intcode value.
+    Overflow(String),
+    /// A position operand that resolves to before the start of memory.
+    NegativeAddress(i64),
+    /// A numeric label that does not match the address of its line.
+    Misplaced {
+        expected: usize,
+        address: usize,
+    },
+}
+
+impl Error for AsmError {}
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            AsmErrorKind::UnknownMnemonic(m) => write!(f, "Unknown mnemonic {}", m),
+            AsmErrorKind::Operands {
+                mnemonic,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} takes {} operands but was given {}",
+                mnemonic, expected, found
+            ),
+            AsmErrorKind::BadOperand(o) => write!(f, "Invalid operand {:?}", o),
+            AsmErrorKind::ImmediateWrite(m) => {
+                write!(f, "{} cannot write to an immediate operand", m)
+            }
+            AsmErrorKind::BadLabel(l) => write!(f, "Invalid label {:?}", l),
+            AsmErrorKind::DuplicateLabel(l) => write!(f, "Label {} is defined twice", l),
+            AsmErrorKind::UnknownLabel(l) => write!(f, "Label {} is never defined", l),
+            AsmErrorKind::Overflow(v) => write!(f, "{} does not fit in an Intcode value", v),
+            AsmErrorKind::NegativeAddress(a) => write!(f, "Address {} is negative", a),
+            AsmErrorKind::Misplaced { expected, address } => write!(
+                f,
+                "Expected address {} but the line is at {}",
+                expected, address
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::disasm::disassemble;
+    use crate::intcode::Registers;
+
+    #[test]
+    fn test_assemble() {
+        let program = assemble(
+            "
+                    IN [n]      ; count down from the input
+            loop:   OUT [n]
+                    add [n], #-1, [n]
+                    JT [n], #loop
+                    HLT
+            n:      DATA 0
+            ",
+        );
+        assert_eq!(
+            Ok(vec![3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0]),
+            program
+        );
+
+        assert_eq!(
+            Ok(vec![109, -3, 204, 2, 21101, 5, -6, 0, 3, 7, 10, 11, 13]),
+            assemble("ARB #-3\nOUT rb+2\nADD #5, #-6, rb\nIN [7]\nDATA end-3, end-2, end\nend:")
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source| assemble(source).map(|_| ()).map_err(|e| e.to_string());
+        assert_eq!(
+            Err("line 2: Unknown mnemonic NOP".to_owned()),
+            error("HLT\nNOP")
+        );
+        assert_eq!(
+            Err("line 1: ADD takes 3 operands but was given 2".to_owned()),
+            error("ADD [1], [2]")
+        );
+        assert_eq!(
+            Err("line 1: IN cannot write to an immediate operand".to_owned()),
+            error("IN #1")
+        );
+        assert_eq!(
+            Err("line 1: Invalid operand \"1\"".to_owned()),
+            error("OUT 1")
+        );
+        assert_eq!(
+            Err("line 1: Label nowhere is never defined".to_owned()),
+            error("JT #1, #nowhere")
+        );
+        assert_eq!(
+            Err("line 2: Label a is defined twice".to_owned()),
+            error("a: HLT\na: HLT")
+        );
+        assert_eq!(
+            Err("line 2: Expected address 4 but the line is at 1".to_owned()),
+            error("0: HLT\n4: HLT")
+        );
+        assert_eq!(
+            Err("line 2: a+9223372036854775807 does not fit in an Intcode value".to_owned()),
+            error("HLT\na: DATA a+9223372036854775807")
+        );
+        assert_eq!(
+            Err("line 1: Address -1 is negative".to_owned()),
+            error("OUT [-1]")
+        );
+        assert_eq!(
+            Err("line 1: Address -3 is negative".to_owned()),
+            error("a: IN [a-3]")
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let programs = [
+            "1,9,10,3,2,3,11,0,99,30,40,50",
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+            "1105,1,7,1,1,1,1,1106,0,11,77,99,1,2",
+            "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,\
+             20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+            // Stray mode digits make a cell data rather than an instruction.
+            "1104,5,99",
+            "10099",
+            // A jump into the middle of an instruction.
+            "1106,0,2,0,0,0,99",
+        ];
+        for program in programs.iter() {
+            let registers = Registers::from_program(program).expect("Failed to parse program");
+            let cells = registers.range(0, registers.len() - 1).unwrap();
+            for follow in [false, true].iter() {
+                let listing = disassemble(&registers, *follow).to_string();
+                assert_eq!(Ok(cells.clone()), assemble(&listing), "{}", listing);
+            }
+        }
+    }
+}
@@ -0,0 +1,477 @@
+//! A line-oriented debugger for Intcode programs. Commands are read one per
+//! line, so a session can be typed at a terminal or scripted.
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use super::history::History;
+use super::snapshot::Snapshot;
+use super::{Machine, OpCode, RegisterErr, Run};
+
+pub const HELP: &str = "\
+commands:
+  step [n]           execute n instructions, printing each (default 1)
+  continue           run until a breakpoint, watchpoint, halt or missing input
+  rstep [n]          undo n instructions, printing each (default 1)
+  rcontinue          undo instructions back to a breakpoint or watchpoint
+  writer <addr>      show the last instruction to write to addr
+  break [addr]       stop when the instruction pointer reaches addr, or list breakpoints
+  delete <addr>      remove a breakpoint
+  watch [addr]       stop when the cell at addr changes, or list watchpoints
+  unwatch <addr>     remove a watchpoint
+  mem <start> [end]  dump memory from start to end inclusive
+  regs               show the instruction pointer, relative base and next instruction
+  input <value>...   queue values for the program to read
+  save <path>        write the machine's state to a snapshot file
+  load <path>        restore the machine's state from a snapshot file
+  help               show this message
+  quit               end the session";
+
+/// Memory dumps list this many cells to a line.
+const CELLS_PER_LINE: usize = 8;
+
+/// The number of instructions that can be stepped back through.
+const HISTORY: usize = 100_000;
+
+pub struct Debugger {
+    machine: Machine,
+    history: History,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+}
+
+impl Debugger {
+    pub fn new(machine: Machine) -> Self {
+        Self {
+            machine,
+            history: History::new(HISTORY),
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    /// Executes commands from `input` until it ends or a `quit`, writing
+    /// `prompt` before each one.
+    pub fn run<R: BufRead, W: Write>(
+        &mut self,
+        input: R,
+        mut output: W,
+        prompt: &str,
+    ) -> io::Result<()> {
+        write!(output, "{}", prompt)?;
+        output.flush()?;
+        for line in input.lines() {
+            if !self.execute(&line?, &mut output)? {
+                break;
+            }
+            write!(output, "{}", prompt)?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Executes a single command, returning `false` once asked to quit.
+    /// Mistakes in the command are reported to `out` rather than returned.
+    pub fn execute(&mut self, command: &str, out: &mut dyn Write) -> io::Result<bool> {
+        let mut words = command.split_whitespace();
+        let name = match words.next() {
+            Some(name) => name,
+            None => return Ok(true),
+        };
+        let args: Vec<&str> = words.collect();
+
+        let result = match name {
+            "step" | "s" => arg(&args, 0, 1).map(|n: usize| self.step_n(n, out)),
+            "continue" | "c" => Ok(self.cont(out)),
+            "rstep" | "rs" => arg(&args, 0, 1).map(|n: usize| self.reverse_step_n(n, out)),
+            "rcontinue" | "rc" => Ok(self.reverse_cont(out)),
+            "writer" => required(&args, 0).map(|addr| self.writer(addr, out)),
+            "break" | "b" if args.is_empty() => Ok(list(out, "breakpoints", &self.breakpoints)),
+            "break" | "b" => required(&args, 0).map(|addr| {
+                self.breakpoints.insert(addr);
+                writeln!(out, "breakpoint at {}", addr)
+            }),
+            "delete" | "d" => required(&args, 0).map(|addr| {
+                self.breakpoints.remove(&addr);
+                Ok(())
+            }),
+            "watch" | "w" if args.is_empty() => Ok(list(out, "watchpoints", &self.watchpoints)),
+            "watch" | "w" => required(&args, 0).map(|addr| {
+                self.watchpoints.insert(addr);
+                writeln!(out, "watching {}", addr)
+            }),
+            "unwatch" => required(&args, 0).map(|addr| {
+                self.watchpoints.remove(&addr);
+                Ok(())
+            }),
+            "mem" | "x" => required(&args, 0)
+                .and_then(|start| arg(&args, 1, start).map(|end| self.dump(start, end, out))),
+            "regs" | "r" => Ok(self.regs(out)),
+            "input" | "i" => args
+                .iter()
+                .map(|a| {
+                    a.parse()
+                        .map_err(|_| format!("{:?} is not an Intcode value", a))
+                })
+                .collect::<Result<Vec<i64>, _>>()
+                .map(|values| {
+                    self.machine.feed_all(values);
+                    Ok(())
+                }),
+            "save" => {
+                required(&args, 0).map(|path: String| match self.machine.snapshot().save(&path) {
+                    Ok(()) => writeln!(out, "saved {}", path),
+                    Err(e) => writeln!(out, "error: {}", e),
+                })
+            }
+            "load" => required(&args, 0).map(|path: String| match Snapshot::load(&path) {
+                Ok(snapshot) => {
+                    self.machine.restore(&snapshot);
+                    self.history.clear();
+                    self.regs(out)
+                }
+                Err(e) => writeln!(out, "error: {}", e),
+            }),
+            "help" | "h" => Ok(writeln!(out, "{}", HELP)),
+            "quit" | "q" => return Ok(false),
+            _ => Err(format!("Unknown command {}, try help", name)),
+        };
+        match result {
+            Ok(written) => written?,
+            Err(message) => writeln!(out, "error: {}", message)?,
+        }
+        Ok(true)
+    }
+
+    fn step_n(&mut self, n: usize, out: &mut dyn Write) -> io::Result<()> {
+        for _ in 0..n {
+            self.instruction(out)?;
+            if self.step(out)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn cont(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        while !self.step(out)? {
+            let position = self.machine.state().position;
+            if self.breakpoints.contains(&position) {
+                return writeln!(out, "breakpoint at {}", position);
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes one instruction, returning whether execution should stop.
+    fn step(&mut self, out: &mut dyn Write) -> io::Result<bool> {
+        let position = self.machine.state().position;
+        let watched: Vec<(usize, i64)> = self
+            .watchpoints
+            .iter()
+            .map(|address| (*address, self.machine.peek(*address).unwrap_or(0)))
+            .collect();
+
+        let mut stop = true;
+        match self.history.step(&mut self.machine) {
+            Run::Continue(_) => stop = false,
+            Run::Output(value) => {
+                writeln!(out, "output {}", value)?;
+                stop = false;
+            }
+            Run::Finished => writeln!(out, "halted at {}", position)?,
+            Run::NeedsInput => writeln!(out, "waiting for input at {}", position)?,
+            Run::Exhausted { steps, .. } => {
+                writeln!(out, "budget spent at {} after {} steps", position, steps)?
+            }
+            Run::Looped { steps, .. } => {
+                writeln!(out, "looping at {} after {} steps", position, steps)?
+            }
+            Run::Error(e) => writeln!(out, "error: {}", chain(&e))?,
+        }
+
+        for (address, old) in watched {
+            let new = self.machine.peek(address).unwrap_or(0);
+            if new != old {
+                writeln!(out, "watch {}: {} -> {}", address, old, new)?;
+                stop = true;
+            }
+        }
+        Ok(stop)
+    }
+
+    fn reverse_step_n(&mut self, n: usize, out: &mut dyn Write) -> io::Result<()> {
+        for _ in 0..n {
+            if self.reverse_step(true, out)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn reverse_cont(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        while !self.reverse_step(false, out)? {
+            let position = self.machine.state().position;
+            if self.breakpoints.contains(&position) {
+                return writeln!(out, "breakpoint at {}", position);
+            }
+        }
+        Ok(())
+    }
+
+    /// Undoes one instruction, printing it if `show`, and returns whether
+    /// stepping back should stop: at the start of the history or after
+    /// undoing a watched write.
+    fn reverse_step(&mut self, show: bool, out: &mut dyn Write) -> io::Result<bool> {
+        let write = match self.history.undo(&mut self.machine) {
+            Some(write) => write,
+            None => {
+                let position = self.machine.state().position;
+                writeln!(out, "start of history at {}", position)?;
+                return Ok(true);
+            }
+        };
+        if show {
+            self.instruction(out)?;
+        }
+        match write {
+            Some(change) if self.watchpoints.contains(&change.address) => {
+                writeln!(
+                    out,
+                    "watch {}: {} -> {}",
+                    change.address, change.new, change.old
+                )?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn writer(&self, address: usize, out: &mut dyn Write) -> io::Result<()> {
+        match self.history.last_write(address) {
+            Some((position, ago, change)) => writeln!(
+                out,
+                "{} written at {} {} steps ago: {} -> {}",
+                address, position, ago, change.old, change.new
+            ),
+            None => writeln!(
+                out,
+                "{} not written in the last {} steps",
+                address,
+                self.history.len()
+            ),
+        }
+    }
+
+    /// Writes the instruction at the instruction pointer.
+    fn instruction(&self, out: &mut dyn Write) -> io::Result<()> {
+        let position = self.machine.state().position;
+        match OpCode::parse(position, self.machine.registers()) {
+            Ok(op) => writeln!(out, "{:>5}: {}", position, op),
+            Err(e) => writeln!(out, "{:>5}: ({})", position, chain(&e)),
+        }
+    }
+
+    fn regs(&self, out: &mut dyn Write) -> io::Result<()> {
+        let state = self.machine.state();
+        writeln!(out, "ip {} rb {}", state.position, state.relative_base)?;
+        self.instruction(out)
+    }
+
+    fn dump(&self, start: usize, end: usize, out: &mut dyn Write) -> io::Result<()> {
+        let registers = self.machine.registers();
+        if end < start {
+            return writeln!(out, "error: End {} is before start {}", end, start);
+        }
+        if end >= registers.limit() {
+            return writeln!(out, "error: {}", RegisterErr::MissingRange(start, end));
+        }
+        // Cells are read a line at a time, as the range can span far more
+        // memory than the program has ever touched.
+        for line in (start..=end).step_by(CELLS_PER_LINE) {
+            let last = end.min(line + CELLS_PER_LINE - 1);
+            let values: Vec<String> = (line..=last)
+                .map(|address| registers.at(address).copied().unwrap_or(0).to_string())
+                .collect();
+            writeln!(out, "{:>5}: {}", line, values.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The argument at `index`, or `default` if there are not that many.
+fn arg<T: FromStr>(args: &[&str], index: usize, default: T) -> Result<T, String> {
+    match args.get(index) {
+        Some(arg) => arg
+            .parse()
+            .map_err(|_| format!("Invalid argument {:?}", arg)),
+        None => Ok(default),
+    }
+}
+
+fn required<T: FromStr>(args: &[&str], index: usize) -> Result<T, String> {
+    let arg = args
+        .get(index)
+        .ok_or_else(|| format!("Missing argument {}", index + 1))?;
+    arg.parse()
+        .map_err(|_| format!("Invalid argument {:?}", arg))
+}
+
+fn list(out: &mut dyn Write, what: &str, addresses: &BTreeSet<usize>) -> io::Result<()> {
+    let addresses: Vec<String> = addresses.iter().map(usize::to_string).collect();
+    writeln!(out, "{}: {}", what, addresses.join(", "))
+}
+
+/// An error followed by each of its causes.
+fn chain(e: &dyn Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn session(program: &str, commands: &str) -> String {
+        let machine = Machine::from_program(program).expect("Failed to parse program");
+        let mut output = Vec::new();
+        Debugger::new(machine)
+            .run(commands.as_bytes(), &mut output, "")
+            .expect("Session failed");
+        String::from_utf8(output).expect("Output is not UTF-8")
+    }
+
+    #[test]
+    fn test_session() {
+        let commands = "break 4
+continue
+regs
+watch 0
+step
+mem 0 3
+continue
+step
+quit
+step";
+        assert_eq!(
+            "breakpoint at 4
+breakpoint at 4
+ip 4 rb 0
+    4: MUL [3], [11], [0]
+watching 0
+    4: MUL [3], [11], [0]
+watch 0: 1 -> 3500
+    0: 3500, 9, 10, 70
+halted at 8
+    8: HLT
+halted at 8
+",
+            session("1,9,10,3,2,3,11,0,99,30,40,50", commands)
+        );
+    }
+
+    #[test]
+    fn test_input_and_errors() {
+        let commands = "continue
+input 7 8
+continue
+bogus
+mem x
+input y
+break
+watch
+mem 0 9
+mem 5 2
+mem 0 1073741824
+";
+        assert_eq!(
+            "waiting for input at 0
+output 7
+output 8
+waiting for input at 0
+error: Unknown command bogus, try help
+error: Invalid argument \"x\"
+error: \"y\" is not an Intcode value
+breakpoints: \nwatchpoints: \n    0: 3, 9, 4, 9, 1105, 1, 0, 0
+    8: 0, 8
+error: End 2 is before start 5
+error: Range [0, 1073741824] reaches beyond the limit
+",
+            session("3,9,4,9,1105,1,0,0,0,0", commands)
+        );
+    }
+
+    #[test]
+    fn test_reverse() {
+        let commands = "watch 0
+break 4
+continue
+continue
+writer 3
+writer 0
+writer 9
+rcontinue
+rcontinue
+step 2
+unwatch 0
+rcontinue
+rstep
+mem 0 3
+rstep";
+        assert_eq!(
+            "watching 0
+breakpoint at 4
+breakpoint at 4
+watch 0: 1 -> 3500
+3 written at 0 2 steps ago: 3 -> 70
+0 written at 4 1 steps ago: 1 -> 3500
+9 not written in the last 2 steps
+watch 0: 3500 -> 1
+start of history at 0
+    0: ADD [9], [10], [3]
+    4: MUL [3], [11], [0]
+watch 0: 1 -> 3500
+breakpoint at 4
+    0: ADD [9], [10], [3]
+    0: 1, 9, 10, 3
+start of history at 0
+",
+            session("1,9,10,3,2,3,11,0,99,30,40,50", commands)
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("aoc2k19-debugger-{}", std::process::id()));
+        let path = path.display();
+        let commands = format!("step\nsave {0}\ncontinue\nload {0}\nmem 0", path);
+        let output = session("1,9,10,3,2,3,11,0,99,30,40,50", &commands);
+        std::fs::remove_file(path.to_string()).expect("Failed to clean up");
+        assert_eq!(
+            format!(
+                "    0: ADD [9], [10], [3]
+saved {}
+halted at 8
+ip 4 rb 0
+    4: MUL [3], [11], [0]
+    0: 1
+",
+                path
+            ),
+            output
+        );
+        assert!(session("99", "load /nonexistent/snapshot")
+            .starts_with("error: I/O failure on /nonexistent/snapshot"));
+    }
+}
@@ -0,0 +1,237 @@
+//! Turns Intcode memory back into a readable listing of instructions.
+
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt;
+
+use super::{OpCode, Param, Registers};
+
+/// Runs of data are split into lines of at most this many cells.
+const DATA_PER_LINE: usize = 8;
+
+/// One line of a listing, starting at the given address.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Line {
+    Code(usize, OpCode),
+    /// Cells that do not hold a valid instruction.
+    Data(usize, Vec<i64>),
+    /// Cells no followed path reaches. Code only reached through a jump to a
+    /// computed address ends up here too.
+    Unreachable(usize, Vec<i64>),
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let data = |f: &mut fmt::Formatter, address: &usize, values: &[i64]| {
+            let values: Vec<String> = values.iter().map(i64::to_string).collect();
+            write!(f, "{:>5}: DATA {}", address, values.join(", "))
+        };
+        match self {
+            Self::Code(address, op) => write!(f, "{:>5}: {}", address, op),
+            Self::Data(address, values) => data(f, address, values),
+            Self::Unreachable(address, values) => {
+                data(f, address, values)?;
+                write!(f, " ; unreachable")
+            }
+        }
+    }
+}
+
+/// A disassembled program, displayed one line per instruction.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Listing(pub Vec<Line>);
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.iter().try_for_each(|line| writeln!(f, "{}", line))
+    }
+}
+
+/// Lists the program in `registers` from address 0.
+///
+/// Without `follow` every cell is decoded in turn, so data that happens to
+/// look like an instruction is listed as one. With `follow` only the
+/// instructions reachable from address 0 are decoded, by falling through and
+/// taking jumps to immediate targets, and everything else is unreachable.
+pub fn disassemble(registers: &Registers, follow: bool) -> Listing {
+    let starts = if follow {
+        reachable(registers)
+    } else {
+        BTreeSet::new()
+    };
+    let mut lines = Vec::new();
+    let mut data = Vec::new();
+    let mut address = 0;
+
+    while address < registers.len() {
+        let op = if follow && !starts.contains(&address) {
+            None
+        } else {
+            decode(registers, address)
+        };
+        // A jump into the middle of an instruction starts another, so the
+        // cells before it are listed as data to keep every address in place.
+        let width = op.as_ref().map_or(1, OpCode::width);
+        let inner = starts.range(address + 1..address + width).next().copied();
+        match (op, inner) {
+            (Some(op), None) => {
+                flush(&mut lines, &mut data);
+                lines.push(Line::Code(address, op));
+                address += width;
+            }
+            (_, inner) => {
+                let end = inner.unwrap_or(address + 1);
+                let unreachable = follow && !starts.contains(&address);
+                if data.first().is_some_and(|(_, _, u)| *u != unreachable) {
+                    flush(&mut lines, &mut data);
+                }
+                data.extend((address..end).map(|address| {
+                    let value = *registers.at(address).unwrap_or(&0);
+                    (address, value, unreachable)
+                }));
+                address = end;
+            }
+        }
+    }
+    flush(&mut lines, &mut data);
+    Listing(lines)
+}
+
+/// Moves a pending run of data cells, each with its address and whether it
+/// is unreachable, into `lines`.
+fn flush(lines: &mut Vec<Line>, data: &mut Vec<(usize, i64, bool)>) {
+    for chunk in data.chunks(DATA_PER_LINE) {
+        let (address, _, unreachable) = chunk[0];
+        let values = chunk.iter().map(|(_, value, _)| *value).collect();
+        lines.push(if unreachable {
+            Line::Unreachable(address, values)
+        } else {
+            Line::Data(address, values)
+        });
+    }
+    data.clear();
+}
+
+/// The instruction at `address`, if it is valid, fits in the program and
+/// assembles back to the same cell. Cells with stray mode digits are data, so
+/// that listings reassemble to the program they came from.
+fn decode(registers: &Registers, address: usize) -> Option<OpCode> {
+    let cell = *registers.at(address).ok()?;
+    OpCode::parse(address, registers)
+        .ok()
+        .filter(|op| address + op.width() <= registers.len() && op.code() == cell)
+}
+
+/// The addresses of every instruction reachable from address 0.
+fn reachable(registers: &Registers) -> BTreeSet<usize> {
+    let mut starts = BTreeSet::new();
+    let mut pending = vec![0];
+    while let Some(address) = pending.pop() {
+        if address >= registers.len() || !starts.insert(address) {
+            continue;
+        }
+        let op = match decode(registers, address) {
+            Some(op) => op,
+            None => continue,
+        };
+        let next = address + op.width();
+        let (cond, target, jumps_when) = match op {
+            OpCode::Stop => continue,
+            OpCode::JumpIfTrue(cond, target) => (cond, target, true),
+            OpCode::JumpIfFalse(cond, target) => (cond, target, false),
+            _ => {
+                pending.push(next);
+                continue;
+            }
+        };
+        let jumps = match cond {
+            Param::Immediate(value) => Some((value != 0) == jumps_when),
+            _ => None,
+        };
+        if jumps != Some(false) {
+            if let Param::Immediate(target) = target {
+                pending.extend(usize::try_from(target).ok());
+            }
+        }
+        if jumps != Some(true) {
+            pending.push(next);
+        }
+    }
+    starts
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn listing(program: &str, follow: bool) -> String {
+        let registers = Registers::from_program(program).expect("Failed to parse program");
+        disassemble(&registers, follow).to_string()
+    }
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!(
+            "    0: ADD [9], [10], [3]
+    4: MUL [3], [11], [0]
+    8: HLT
+    9: DATA 30, 40, 50
+",
+            listing("1,9,10,3,2,3,11,0,99,30,40,50", false)
+        );
+
+        assert_eq!(
+            "    0: ARB #-3
+    2: OUT rb+2
+    4: ADD #5, #-6, rb-1
+    8: IN [7]
+   10: HLT
+",
+            listing("109,-3,204,2,21101,5,-6,-1,3,7,99", false)
+        );
+    }
+
+    #[test]
+    fn test_follow() {
+        let program = "1105,1,7,1,1,1,1,1106,0,11,77,99,1,2";
+        assert_eq!(
+            "    0: JT #1, #7
+    3: ADD [1], [1], [1]
+    7: JF #0, #11
+   10: DATA 77
+   11: HLT
+   12: DATA 1, 2
+",
+            listing(program, false)
+        );
+        assert_eq!(
+            "    0: JT #1, #7
+    3: DATA 1, 1, 1, 1 ; unreachable
+    7: JF #0, #11
+   10: DATA 77 ; unreachable
+   11: HLT
+   12: DATA 1, 2 ; unreachable
+",
+            listing(program, true)
+        );
+
+        assert_eq!(
+            "    0: JT [5], #6
+    3: OUT #1
+    5: DATA 0
+    6: HLT
+",
+            listing("1005,5,6,104,1,0,99", true)
+        );
+
+        // Jumping into the middle of an instruction lists the outer one as
+        // data, as its cells are both code and operands.
+        assert_eq!(
+            "    0: DATA 1106, 0
+    2: MUL [0], [0], [0]
+    6: HLT
+",
+            listing("1106,0,2,0,0,0,99", true)
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! An undo log for a running machine, so that it can be stepped backwards
+//! and asked which instruction last wrote a cell.
+
+use std::collections::VecDeque;
+
+use super::trace::{Change, Event, Tracer};
+use super::{EvalStep, Machine, OpCode, Run};
+
+/// What one instruction changed, enough to put the machine back to how it
+/// was before it ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+    state: EvalStep,
+    write: Option<Change>,
+    /// The input value the instruction consumed, if any.
+    input: Option<i64>,
+}
+
+/// The most recent instructions a machine ran, up to a fixed number.
+#[derive(Debug, Clone)]
+pub struct History {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+}
+
+impl History {
+    /// Keeps the last `capacity` instructions, forgetting older ones.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// The number of instructions that can be undone.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Executes a single instruction of `machine` like `Machine::step`,
+    /// recording how to undo it if it ran.
+    pub fn step(&mut self, machine: &mut Machine) -> Run {
+        let state = machine.state();
+        let mut recorder = Recorder::default();
+        let res = machine.step_traced(&mut recorder);
+        if let Run::Continue(_) | Run::Output(_) = res {
+            if self.entries.len() == self.capacity {
+                self.entries.pop_front();
+            }
+            if self.capacity > 0 {
+                self.entries.push_back(Entry {
+                    state,
+                    write: recorder.write,
+                    input: recorder.input,
+                });
+            }
+        }
+        res
+    }
+
+    /// Puts `machine` back to how it was before the last instruction
+    /// recorded, returning the cell that instruction wrote, if any, or
+    /// `None` once there is no history left. Input the instruction read is
+    /// queued again, but output it produced stays with whoever took it.
+    pub fn undo(&mut self, machine: &mut Machine) -> Option<Option<Change>> {
+        let entry = self.entries.pop_back()?;
+        if let Some(change) = entry.write {
+            machine
+                .poke(change.address, change.old)
+                .expect("Cells written to can be restored");
+        }
+        if let Some(value) = entry.input {
+            machine.input.push_front(value);
+        }
+        machine.state = entry.state;
+        machine.watchdog.forget();
+        machine.watchdog.unran();
+        Some(entry.write)
+    }
+
+    /// The position of the most recent instruction to write to `address`,
+    /// how many instructions ago that was, and what it changed.
+    pub fn last_write(&self, address: usize) -> Option<(usize, usize, Change)> {
+        self.entries
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(ago, entry)| match entry.write {
+                Some(change) if change.address == address => {
+                    Some((entry.state.position, ago + 1, change))
+                }
+                _ => None,
+            })
+    }
+}
+
+/// Keeps the cell an instruction wrote and any input it read.
+#[derive(Default)]
+struct Recorder {
+    write: Option<Change>,
+    input: Option<i64>,
+}
+
+impl Tracer for Recorder {
+    fn record(&mut self, event: &Event) {
+        self.write = event.write;
+        if let (OpCode::Input(_), Some(change)) = (event.op, event.write) {
+            self.input = Some(change.new);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::watchdog::Watchdog;
+
+    #[test]
+    fn test_undo() {
+        // Reads a value, adds 5 to it in place and outputs it.
+        let mut machine = Machine::from_program("3,9,1001,9,5,9,4,9,99").expect("Bad program");
+        machine.feed(10);
+        let mut history = History::new(10);
+        assert!(matches!(history.step(&mut machine), Run::Continue(_)));
+        assert!(matches!(history.step(&mut machine), Run::Continue(_)));
+        assert!(matches!(history.step(&mut machine), Run::Output(15)));
+        assert!(matches!(history.step(&mut machine), Run::Finished));
+        assert_eq!(3, history.len());
+        assert_eq!(3, machine.steps());
+
+        assert_eq!(
+            Some((
+                2,
+                2,
+                Change {
+                    address: 9,
+                    old: 10,
+                    new: 15
+                }
+            )),
+            history.last_write(9)
+        );
+        assert_eq!(None, history.last_write(0));
+
+        assert_eq!(Some(None), history.undo(&mut machine));
+        assert_eq!(6, machine.state().position);
+        let change = history.undo(&mut machine).expect("History ran out");
+        assert_eq!(Some(10), change.map(|change| change.old));
+        assert_eq!(Some(10), machine.peek(9).ok());
+        history.undo(&mut machine).expect("History ran out");
+        assert_eq!(0, machine.state().position);
+        assert_eq!(Some(0), machine.peek(9).ok());
+        assert_eq!(None, history.undo(&mut machine));
+        assert_eq!(0, machine.steps());
+
+        // The input read is queued again, so running forward repeats itself.
+        assert!(matches!(machine.resume(), Run::Output(15)));
+    }
+
+    #[test]
+    fn test_undo_budget() {
+        // Undone instructions are taken off the count, so stepping back and
+        // forth does not spend the budget.
+        let mut machine = Machine::from_program("1101,1,1,9,1101,2,2,9,99")
+            .expect("Bad program")
+            .with_watchdog(Watchdog::new().with_budget(2));
+        let mut history = History::new(10);
+        for _ in 0..3 {
+            assert!(matches!(history.step(&mut machine), Run::Continue(_)));
+            history.undo(&mut machine).expect("History ran out");
+        }
+        assert!(matches!(history.step(&mut machine), Run::Continue(_)));
+        assert!(matches!(history.step(&mut machine), Run::Continue(_)));
+        assert_eq!(2, machine.steps());
+        assert!(matches!(
+            history.step(&mut machine),
+            Run::Exhausted {
+                position: 8,
+                steps: 2
+            }
+        ));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut machine = Machine::from_program("1101,1,1,9,1101,2,2,9,99").expect("Bad program");
+        let mut history = History::new(1);
+        history.step(&mut machine);
+        history.step(&mut machine);
+        assert_eq!(1, history.len());
+        assert_eq!(
+            Some(2),
+            history.last_write(9).map(|(_, _, change)| change.old)
+        );
+        history.undo(&mut machine).expect("History ran out");
+        assert_eq!(4, machine.state().position);
+        assert_eq!(None, history.undo(&mut machine));
+    }
+}
@@ -0,0 +1,387 @@
+//! Saved machine states, which can be restored to try something else from
+//! the same point or written to a file and loaded back later.
+//!
+//! A snapshot file is text with one field per line:
+//!
+//! ```text
+//! intcode snapshot
+//! ip 4
+//! rb 0
+//! limit 1073741824
+//! input 5, 6
+//! output
+//! memory 1, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50
+//! sparse 500000=9
+//! ```
+//!
+//! `memory` holds the densely stored cells from address 0 and `sparse` any
+//! others that are not zero. `limit`, `input`, `output` and `sparse` may be
+//! left out.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::iter::FromIterator;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{EvalStep, Machine, RegisterErr, Registers, DEFAULT_LIMIT};
+use crate::error;
+
+const HEADER: &str = "intcode snapshot";
+
+/// Everything a machine needs to carry on from where it was: its memory,
+/// instruction pointer, relative base, and input and output that are still
+/// queued. Its watchdog is not included.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    registers: Registers,
+    state: EvalStep,
+    input: VecDeque<i64>,
+    output: Vec<i64>,
+}
+
+impl Machine {
+    /// Saves the machine's state. Snapshots copy the memory, which for
+    /// Intcode programs is small enough to make this cheap.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            registers: self.registers.clone(),
+            state: self.state,
+            input: self.input.clone(),
+            output: self.output.clone(),
+        }
+    }
+
+    /// Puts the machine back to the state in `snapshot`. The watchdog keeps
+    /// counting steps but forgets the states it has seen.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.registers = snapshot.registers.clone();
+        self.state = snapshot.state;
+        self.input = snapshot.input.clone();
+        self.output = snapshot.output.clone();
+        self.watchdog.forget();
+    }
+}
+
+impl Snapshot {
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn state(&self) -> EvalStep {
+        self.state
+    }
+
+    /// Loads a snapshot saved with `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, error::Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| error::Error::input(path, e))?;
+        text.parse()
+            .map_err(|e| error::Error::parse(format!("Invalid snapshot {}", path.display()), e))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), error::Error> {
+        let path = path.as_ref();
+        fs::write(path, self.to_string()).map_err(|e| error::Error::input(path, e))
+    }
+}
+
+impl From<Snapshot> for Machine {
+    fn from(snapshot: Snapshot) -> Self {
+        let mut machine = Machine::new(snapshot.registers);
+        machine.state = snapshot.state;
+        machine.input = snapshot.input;
+        machine.output = snapshot.output;
+        machine
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sparse = self
+            .registers
+            .sparse
+            .iter()
+            .filter(|(_, val)| **val != 0)
+            .map(|(num, val)| format!("{}={}", num, val));
+        let fields = [
+            ("ip", self.state.position.to_string()),
+            ("rb", self.state.relative_base.to_string()),
+            ("limit", self.registers.limit.to_string()),
+            ("input", join(&self.input)),
+            ("output", join(&self.output)),
+            ("memory", join(&self.registers.dense)),
+            ("sparse", join(sparse)),
+        ];
+        writeln!(f, "{}", HEADER)?;
+        for (name, value) in &fields {
+            if value.is_empty() {
+                writeln!(f, "{}", name)?;
+            } else {
+                writeln!(f, "{} {}", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn join<I: IntoIterator>(values: I) -> String
+where
+    I::Item: fmt::Display,
+{
+    let values: Vec<String> = values.into_iter().map(|v| v.to_string()).collect();
+    values.join(", ")
+}
+
+impl FromStr for Snapshot {
+    type Err = SnapshotError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()));
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            _ => {
+                return Err(SnapshotError {
+                    line: 1,
+                    kind: SnapshotErrorKind::Header,
+                })
+            }
+        }
+
+        let mut fields = Fields::default();
+        let mut last = 1;
+        for (line, text) in lines.filter(|(_, text)| !text.is_empty()) {
+            last = line;
+            fields
+                .set(line, text)
+                .map_err(|kind| SnapshotError { line, kind })?;
+        }
+        fields.build().map_err(|(line, kind)| SnapshotError {
+            line: line.unwrap_or(last),
+            kind,
+        })
+    }
+}
+
+/// The fields read so far, each of which may only be given once.
+#[derive(Default)]
+struct Fields {
+    ip: Option<usize>,
+    rb: Option<i64>,
+    limit: Option<usize>,
+    input: Option<VecDeque<i64>>,
+    output: Option<Vec<i64>>,
+    memory: Option<Vec<i64>>,
+    /// The sparse cells and the line they are on.
+    sparse: Option<(usize, Vec<(usize, i64)>)>,
+}
+
+impl Fields {
+    fn set(&mut self, line: usize, text: &str) -> Result<(), SnapshotErrorKind> {
+        let (name, value) = text.split_once(' ').unwrap_or((text, ""));
+        let duplicate = match name {
+            "ip" => self.ip.replace(single(value)?).is_some(),
+            "rb" => self.rb.replace(single(value)?).is_some(),
+            "limit" => self.limit.replace(single(value)?).is_some(),
+            "input" => self.input.replace(list(value)?).is_some(),
+            "output" => self.output.replace(list(value)?).is_some(),
+            "memory" => self.memory.replace(list(value)?).is_some(),
+            "sparse" => {
+                let cells = list::<String, Vec<_>>(value)?
+                    .iter()
+                    .map(|cell| {
+                        cell.split_once('=')
+                            .and_then(|(num, val)| Some((num.parse().ok()?, val.parse().ok()?)))
+                            .ok_or_else(|| SnapshotErrorKind::BadValue(cell.clone()))
+                    })
+                    .collect::<Result<_, _>>()?;
+                self.sparse.replace((line, cells)).is_some()
+            }
+            _ => return Err(SnapshotErrorKind::UnknownField(name.to_owned())),
+        };
+        if duplicate {
+            return Err(SnapshotErrorKind::DuplicateField(name.to_owned()));
+        }
+        Ok(())
+    }
+
+    /// The snapshot the fields describe, or an error with the line it is on
+    /// when that is known.
+    fn build(self) -> Result<Snapshot, (Option<usize>, SnapshotErrorKind)> {
+        let missing = |name| (None, SnapshotErrorKind::MissingField(name));
+        let memory = self.memory.ok_or_else(|| missing("memory"))?;
+        let mut registers = Registers::new(memory).with_limit(self.limit.unwrap_or(DEFAULT_LIMIT));
+        if let Some((line, cells)) = self.sparse {
+            for (num, val) in cells {
+                registers
+                    .set(num, val)
+                    .map_err(|e| (Some(line), SnapshotErrorKind::Register(e)))?;
+            }
+        }
+        Ok(Snapshot {
+            registers,
+            state: EvalStep {
+                position: self.ip.ok_or_else(|| missing("ip"))?,
+                relative_base: self.rb.ok_or_else(|| missing("rb"))?,
+            },
+            input: self.input.unwrap_or_default(),
+            output: self.output.unwrap_or_default(),
+        })
+    }
+}
+
+fn single<T: FromStr>(value: &str) -> Result<T, SnapshotErrorKind> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| SnapshotErrorKind::BadValue(value.to_owned()))
+}
+
+/// Comma separated values, of which there may be none.
+fn list<T: FromStr, C: FromIterator<T>>(value: &str) -> Result<C, SnapshotErrorKind> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(single)
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct SnapshotError {
+    pub line: usize,
+    pub kind: SnapshotErrorKind,
+}
+
+#[derive(Debug)]
+pub enum SnapshotErrorKind {
+    Header,
+    UnknownField(String),
+    DuplicateField(String),
+    MissingField(&'static str),
+    BadValue(String),
+    Register(RegisterErr),
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            SnapshotErrorKind::Register(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            SnapshotErrorKind::Header => write!(f, "Expected {:?}", HEADER),
+            SnapshotErrorKind::UnknownField(name) => write!(f, "Unknown field {}", name),
+            SnapshotErrorKind::DuplicateField(name) => write!(f, "Field {} is given twice", name),
+            SnapshotErrorKind::MissingField(name) => write!(f, "Field {} is missing", name),
+            SnapshotErrorKind::BadValue(value) => write!(f, "Invalid value {:?}", value),
+            SnapshotErrorKind::Register(_) => write!(f, "Cannot restore memory"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::Run;
+
+    #[test]
+    fn test_snapshot() {
+        // Reads two values and outputs their sum.
+        let mut machine =
+            Machine::from_program("3,11,3,12,1,11,12,13,4,13,99").expect("Failed to parse program");
+        machine.feed(3);
+        assert!(matches!(machine.run(), Run::NeedsInput));
+        let snapshot = machine.snapshot();
+
+        machine.feed(4);
+        assert!(matches!(machine.run(), Run::Finished));
+        assert_eq!(vec![7], machine.drain_output());
+
+        machine.restore(&snapshot);
+        assert_eq!(2, machine.state().position);
+        machine.feed(10);
+        assert!(matches!(machine.run(), Run::Finished));
+        assert_eq!(vec![13], machine.drain_output());
+
+        let mut fork = Machine::from(snapshot.clone());
+        fork.feed(-3);
+        assert!(matches!(fork.run(), Run::Finished));
+        assert_eq!(vec![0], fork.drain_output());
+    }
+
+    #[test]
+    fn test_text() {
+        let mut machine = Machine::from_program("109,-2,3,11,4,11,99").expect("Bad program");
+        machine
+            .poke(500_000, 9)
+            .expect("Failed to write sparse memory");
+        machine.feed_all(vec![5, 6]);
+        assert!(matches!(machine.step(), Run::Continue(_)));
+        let text = machine.snapshot().to_string();
+        assert_eq!(
+            "intcode snapshot
+ip 2
+rb -2
+limit 1073741824
+input 5, 6
+output
+memory 109, -2, 3, 11, 4, 11, 99
+sparse 500000=9
+",
+            text
+        );
+
+        let mut restored = Machine::from(text.parse::<Snapshot>().expect("Bad snapshot"));
+        assert_eq!(Some(9), restored.peek(500_000).ok());
+        assert!(matches!(restored.run(), Run::Finished));
+        assert_eq!(vec![5], restored.drain_output());
+
+        let path = std::env::temp_dir().join(format!("aoc2k19-snapshot-{}", std::process::id()));
+        machine.snapshot().save(&path).expect("Failed to save");
+        let loaded = Snapshot::load(&path).expect("Failed to load");
+        fs::remove_file(&path).expect("Failed to clean up");
+        assert_eq!(text, loaded.to_string());
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text: &str| text.parse::<Snapshot>().unwrap_err().to_string();
+        assert_eq!("line 1: Expected \"intcode snapshot\"", error("ip 0"));
+        assert_eq!(
+            "line 3: Field memory is missing",
+            error("intcode snapshot\nip 0\nrb 0\n")
+        );
+        assert_eq!(
+            "line 3: Field ip is given twice",
+            error("intcode snapshot\nip 0\nip 1\n")
+        );
+        assert_eq!(
+            "line 2: Unknown field pc",
+            error("intcode snapshot\npc 0\n")
+        );
+        assert_eq!(
+            "line 2: Invalid value \"x\"",
+            error("intcode snapshot\nmemory 1, x\n")
+        );
+        assert_eq!(
+            "line 3: Invalid value \"5\"",
+            error("intcode snapshot\nmemory 99\nsparse 5\n")
+        );
+        assert_eq!(
+            "line 5: Cannot restore memory",
+            error("intcode snapshot\nip 0\nrb 0\nlimit 10\nsparse 20=1\nmemory 99\n")
+        );
+    }
+}
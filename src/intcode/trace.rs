@@ -0,0 +1,265 @@
+//! Records the instructions a program runs, one line each, so that runs of
+//! two versions of a program or two interpreters can be diffed.
+
+use std::fmt;
+use std::io;
+use std::ops::RangeInclusive;
+
+use super::{asm, EvalStep, OpCode, Registers};
+use crate::error::Error;
+
+/// Something told about each instruction as it runs.
+pub trait Tracer {
+    /// Whether to record the instruction at `position`. The values an
+    /// instruction reads and writes are only gathered when this is true.
+    fn wants(&self, _position: usize, _op: &OpCode) -> bool {
+        true
+    }
+
+    fn record(&mut self, event: &Event);
+}
+
+/// No tracing at all.
+impl Tracer for () {
+    fn wants(&self, _position: usize, _op: &OpCode) -> bool {
+        false
+    }
+
+    fn record(&mut self, _event: &Event) {}
+}
+
+/// An instruction that ran, with the values of the parameters it read and
+/// the cell it wrote to.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Event<'a> {
+    pub position: usize,
+    pub op: &'a OpCode,
+    pub reads: Vec<i64>,
+    pub write: Option<Change>,
+}
+
+/// A cell an instruction wrote to, and its value before and after.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub address: usize,
+    pub old: i64,
+    pub new: i64,
+}
+
+impl<'a> Event<'a> {
+    /// Gathers what `op` at `state` is about to read and overwrite.
+    pub fn before(state: &EvalStep, op: &'a OpCode, registers: &Registers) -> Self {
+        let base = state.relative_base;
+        let reads = op
+            .reads()
+            .iter()
+            .map(|param| param.read(registers, base).unwrap_or(0))
+            .collect();
+        let write = op
+            .writes()
+            .and_then(|address| address.resolve(base).ok())
+            .map(|address| {
+                let old = registers.at(address).copied().unwrap_or(0);
+                Change {
+                    address,
+                    old,
+                    new: old,
+                }
+            });
+        Self {
+            position: state.position,
+            op,
+            reads,
+            write,
+        }
+    }
+
+    /// Fills in the value the instruction wrote once it has run.
+    pub fn after(&mut self, registers: &Registers) {
+        if let Some(change) = &mut self.write {
+            change.new = registers.at(change.address).copied().unwrap_or(0);
+        }
+    }
+}
+
+/// Events display as the instruction followed by what it read and wrote,
+/// e.g. `    4: MUL [3], [11], [0] ; reads 70, 50 ; [0] 1 -> 3500`.
+impl fmt::Display for Event<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>5}: {}", self.position, self.op)?;
+        if !self.reads.is_empty() {
+            let reads: Vec<String> = self.reads.iter().map(i64::to_string).collect();
+            write!(f, " ; reads {}", reads.join(", "))?;
+        }
+        if let Some(Change { address, old, new }) = self.write {
+            write!(f, " ; [{}] {} -> {}", address, old, new)?;
+        }
+        Ok(())
+    }
+}
+
+/// Which instructions a trace records.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Filter {
+    /// Only instructions at these addresses, or anywhere when `None`.
+    pub addresses: Option<RangeInclusive<usize>>,
+    /// Only instructions with these upper case mnemonics, or any when empty.
+    pub ops: Vec<String>,
+}
+
+impl Filter {
+    /// Parses an inclusive `<start>-<end>` address range and a list of
+    /// mnemonics, as given on the command line.
+    pub fn parse<S: AsRef<str>>(addresses: Option<&str>, ops: &[S]) -> Result<Self, Error> {
+        let addresses = addresses
+            .map(|range| {
+                let (start, end): (usize, usize) = range
+                    .split_once('-')
+                    .and_then(|(start, end)| {
+                        Some((start.trim().parse().ok()?, end.trim().parse().ok()?))
+                    })
+                    .ok_or_else(|| {
+                        Error::Usage(format!(
+                            "Address range {:?} is not of the form <start>-<end>",
+                            range
+                        ))
+                    })?;
+                if end < start {
+                    return Err(Error::Usage(format!(
+                        "Address range {:?} ends before it starts",
+                        range
+                    )));
+                }
+                Ok(start..=end)
+            })
+            .transpose()?;
+        let ops = ops
+            .iter()
+            .map(|op| {
+                let op = op.as_ref();
+                asm::opcode(op)
+                    .map(|_| op.to_ascii_uppercase())
+                    .ok_or_else(|| Error::Usage(format!("Unknown mnemonic {}", op)))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { addresses, ops })
+    }
+
+    pub fn matches(&self, position: usize, op: &OpCode) -> bool {
+        self.addresses
+            .as_ref()
+            .is_none_or(|range| range.contains(&position))
+            && (self.ops.is_empty() || self.ops.iter().any(|name| name == op.mnemonic()))
+    }
+}
+
+/// Writes a line for each instruction `filter` matches to `out`.
+pub struct Writer<W> {
+    out: W,
+    filter: Filter,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> Writer<W> {
+    pub fn new(out: W, filter: Filter) -> Self {
+        Self {
+            out,
+            filter,
+            error: None,
+        }
+    }
+
+    /// Flushes the trace, returning the first error writing any of it. The
+    /// trace stops at that error, though the program carries on.
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush().map(|_| self.out),
+        }
+    }
+}
+
+impl<W: io::Write> Tracer for Writer<W> {
+    fn wants(&self, position: usize, op: &OpCode) -> bool {
+        self.error.is_none() && self.filter.matches(position, op)
+    }
+
+    fn record(&mut self, event: &Event) {
+        if let Err(e) = writeln!(self.out, "{}", event) {
+            self.error = Some(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{run_traced, Machine, Queue, Run};
+
+    fn trace(program: &str, input: &[i64], filter: Filter) -> String {
+        let mut registers = Registers::from_program(program).expect("Failed to parse program");
+        let mut tracer = Writer::new(Vec::new(), filter);
+        let res = run_traced(&mut registers, &mut Queue::new(input.to_vec()), &mut tracer);
+        assert!(matches!(res, Run::Finished), "{:?}", res);
+        String::from_utf8(tracer.finish().expect("Trace failed")).expect("Trace is not UTF-8")
+    }
+
+    #[test]
+    fn test_trace() {
+        assert_eq!(
+            "    0: ADD [9], [10], [3] ; reads 30, 40 ; [3] 3 -> 70
+    4: MUL [3], [11], [0] ; reads 70, 50 ; [0] 1 -> 3500
+    8: HLT
+",
+            trace("1,9,10,3,2,3,11,0,99,30,40,50", &[], Filter::default())
+        );
+
+        // Reads and writes through the relative base are resolved.
+        assert_eq!(
+            "    0: ARB #5 ; reads 5
+    2: IN rb+2 ; [7] 0 -> 9
+    4: OUT rb+2 ; reads 9
+    6: HLT
+",
+            trace("109,5,203,2,204,2,99", &[9], Filter::default())
+        );
+
+        // A blocked instruction is only recorded once it runs.
+        let mut machine = Machine::from_program("3,3,99").expect("Failed to parse program");
+        let mut tracer = Writer::new(Vec::new(), Filter::default());
+        assert!(matches!(machine.step_traced(&mut tracer), Run::NeedsInput));
+        machine.feed(7);
+        assert!(matches!(machine.step_traced(&mut tracer), Run::Continue(_)));
+        assert_eq!(
+            "    0: IN [3] ; [3] 0 -> 7\n",
+            String::from_utf8(tracer.finish().expect("Trace failed")).expect("Trace is not UTF-8")
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let program = "1,9,10,3,2,3,11,0,99,30,40,50";
+        let filter = Filter::parse(Some("4-20"), &[] as &[&str]).expect("Bad filter");
+        assert_eq!(
+            "    4: MUL [3], [11], [0] ; reads 70, 50 ; [0] 1 -> 3500
+    8: HLT
+",
+            trace(program, &[], filter)
+        );
+        let filter = Filter::parse(None, &["add", "HLT"]).expect("Bad filter");
+        assert_eq!(
+            "    0: ADD [9], [10], [3] ; reads 30, 40 ; [3] 3 -> 70
+    8: HLT
+",
+            trace(program, &[], filter)
+        );
+        let filter = Filter::parse(Some("1-8"), &["add"]).expect("Bad filter");
+        assert_eq!("", trace(program, &[], filter));
+
+        assert!(Filter::parse(Some("4"), &[] as &[&str]).is_err());
+        assert!(Filter::parse(Some("a-b"), &[] as &[&str]).is_err());
+        assert!(Filter::parse(Some("20-4"), &[] as &[&str]).is_err());
+        assert!(Filter::parse(Some("4-4"), &[] as &[&str]).is_ok());
+        assert!(Filter::parse(None, &["NOP"]).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! Stops programs that never halt, either by capping the number of
+//! instructions they may run or by noticing that they have gone round in a
+//! loop they can never leave.
+
+use std::collections::HashSet;
+
+use super::{EvalStep, Registers, Run};
+
+/// Counts the instructions a program runs and checks each one before it
+/// runs. Without a budget or loop detection it only counts.
+#[derive(Debug, Default, Clone)]
+pub struct Watchdog {
+    budget: Option<u64>,
+    detect_loops: bool,
+    steps: u64,
+    /// The instruction pointer, memory fingerprint and relative base before
+    /// every instruction run since input was last read.
+    seen: HashSet<(usize, u64, i64)>,
+    /// The state checked for the instruction about to run.
+    pending: Option<(usize, u64, i64)>,
+}
+
+impl Watchdog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops the program once it has run `steps` instructions.
+    pub fn with_budget(self, steps: u64) -> Self {
+        Self {
+            budget: Some(steps),
+            ..self
+        }
+    }
+
+    /// Stops the program when it is about to run an instruction from exactly
+    /// the same state as before without reading any input in between. Such a
+    /// program would repeat itself forever, output included. Every state is
+    /// remembered until the next input, so long runs should have a budget too.
+    pub fn with_loop_detection(self) -> Self {
+        Self {
+            detect_loops: true,
+            ..self
+        }
+    }
+
+    /// The number of instructions that have run.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The outcome to stop with instead of running the instruction at
+    /// `state`, if the budget is spent or the program is looping.
+    pub fn check(&mut self, state: &EvalStep, registers: &Registers) -> Option<Run> {
+        let position = state.position;
+        let steps = self.steps;
+        if self.budget.is_some_and(|budget| steps >= budget) {
+            return Some(Run::Exhausted { position, steps });
+        }
+        if self.detect_loops {
+            let key = (position, registers.fingerprint(), state.relative_base);
+            if self.seen.contains(&key) {
+                return Some(Run::Looped { position, steps });
+            }
+            self.pending = Some(key);
+        }
+        None
+    }
+
+    /// Forgets every state seen, for a machine that has been put back to an
+    /// earlier state and will pass through them again.
+    pub fn forget(&mut self) {
+        self.seen.clear();
+        self.pending = None;
+    }
+
+    /// Takes back the count of an instruction that has been undone, so that
+    /// running it again is not counted twice against the budget.
+    pub fn unran(&mut self) {
+        self.steps = self.steps.saturating_sub(1);
+    }
+
+    /// Counts the instruction last checked, once it has run. Reading input
+    /// can change what the program does next, so it forgets every state.
+    pub fn ran(&mut self, read_input: bool) {
+        self.steps += 1;
+        let pending = self.pending.take();
+        if read_input {
+            self.seen.clear();
+        } else {
+            self.seen.extend(pending);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{run_watched, Machine, Queue};
+
+    #[test]
+    fn test_budget() {
+        let mut registers = Registers::from_program("1101,1,2,5,99").expect("Bad program");
+        let mut watchdog = Watchdog::new().with_budget(1);
+        let res = run_watched(&mut registers, &mut (), &mut watchdog);
+        assert!(
+            matches!(
+                res,
+                Run::Exhausted {
+                    position: 4,
+                    steps: 1
+                }
+            ),
+            "{:?}",
+            res
+        );
+
+        let mut registers = Registers::from_program("1101,1,2,5,99").expect("Bad program");
+        let mut watchdog = Watchdog::new().with_budget(2);
+        assert!(matches!(
+            run_watched(&mut registers, &mut (), &mut watchdog),
+            Run::Finished
+        ));
+        assert_eq!(1, watchdog.steps());
+    }
+
+    #[test]
+    fn test_loop_detection() {
+        // JT #1, #0 jumps to itself forever.
+        let mut registers = Registers::from_program("1105,1,0").expect("Bad program");
+        let mut watchdog = Watchdog::new().with_loop_detection();
+        let res = run_watched(&mut registers, &mut (), &mut watchdog);
+        assert!(
+            matches!(
+                res,
+                Run::Looped {
+                    position: 0,
+                    steps: 1
+                }
+            ),
+            "{:?}",
+            res
+        );
+
+        // Counting down changes memory, so it is not a loop until the count
+        // is done and the program spins on the final jump.
+        let program = "1001,10,-1,10,1005,10,0,1105,1,7,3";
+        let mut registers = Registers::from_program(program).expect("Bad program");
+        let mut watchdog = Watchdog::new().with_loop_detection();
+        let res = run_watched(&mut registers, &mut (), &mut watchdog);
+        assert!(
+            matches!(
+                res,
+                Run::Looped {
+                    position: 7,
+                    steps: 7
+                }
+            ),
+            "{:?}",
+            res
+        );
+
+        // Echoing input forever is no loop, as every pass reads new input.
+        // Once it runs out the blocked input instruction is not counted.
+        let mut registers = Registers::from_program("3,7,4,7,1105,1,0").expect("Bad program");
+        let mut io = Queue::new(vec![1, 2, 3]);
+        let mut watchdog = Watchdog::new().with_loop_detection();
+        let res = run_watched(&mut registers, &mut io, &mut watchdog);
+        assert!(matches!(res, Run::NeedsInput), "{:?}", res);
+        assert_eq!(vec![1, 2, 3], io.output);
+        assert_eq!(9, watchdog.steps());
+
+        // A machine waiting for input does not see itself as looping when it
+        // retries the input instruction.
+        let mut machine = Machine::from_program("3,7,4,7,1105,1,0")
+            .expect("Bad program")
+            .with_watchdog(Watchdog::new().with_loop_detection());
+        assert!(matches!(machine.run(), Run::NeedsInput));
+        machine.feed(5);
+        assert!(matches!(machine.run(), Run::NeedsInput));
+        assert_eq!(vec![5], machine.drain_output());
+        assert_eq!(3, machine.steps());
+    }
+}
@@ -0,0 +1,21 @@
+//! Solutions to the 2019 Advent of Code.
+//!
+//! Each day lives in its own module and implements [`Challenge`]; the
+//! [`Runner`] collects them, finds their input and checks their answers.
+
+pub mod answers;
+pub mod bench;
+pub mod challenge;
+pub mod error;
+pub mod input;
+pub mod intcode;
+pub mod report;
+pub mod runner;
+
+pub mod one;
+pub mod three;
+pub mod two;
+
+pub use challenge::{Answer, Challenge};
+pub use error::Error;
+pub use runner::Runner;
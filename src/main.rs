@@ -1,19 +1,171 @@
 use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, IsTerminal};
+use std::process;
 
-mod challenge;
-mod input;
-mod runner;
+use aoc2k19::input;
+use aoc2k19::intcode::debugger::Debugger;
+use aoc2k19::intcode::watchdog::Watchdog;
+use aoc2k19::intcode::{disasm, trace, Io, Machine, Registers, Run, Stdio};
+use aoc2k19::report::{Report, Summary};
+use aoc2k19::{Error, Runner};
 
-mod one;
-mod three;
-mod two;
+mod cli;
 
-use runner::Runner;
+use cli::Command;
 
-fn main() -> Result<(), String> {
-    let args: Vec<String> = env::args().collect();
-    match &args[..] {
-        [_, day, part] => Runner::new().run(&*day, &*part).map_err(|e| e.to_string()),
-        _ => Err("`day` and `part` args required".to_string()),
+/// Exit code for runs that completed but produced failing parts or answers
+/// that differ from the recorded ones. Errors use `Error::exit_code`.
+const FAILED_PARTS: i32 = 1;
+
+/// The most instructions a traced or debugged program may run unless
+/// `--budget` says otherwise, so one that never halts cannot run forever.
+const STEP_BUDGET: u64 = 10_000_000;
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        process::exit(e.exit_code());
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let (command, options) = cli::parse(env::args().skip(1)).map_err(Error::Usage)?;
+    match &command {
+        Command::Disasm(path) => {
+            let registers = program(path)?;
+            print!("{}", disasm::disassemble(&registers, options.follow));
+            return Ok(());
+        }
+        Command::Debug(path) => return debug(path, &options),
+        Command::Trace(path, file) => return run_trace(path, file, &options),
+        _ => {}
+    }
+
+    let runner = match options.input.as_deref() {
+        None => Runner::new(),
+        Some("-") => Runner::with_input(input::Source::stdin()?),
+        Some(path) => Runner::with_input(input::Source::File(path.into())),
+    }
+    .with_params(input::Params::parse(&options.params)?);
+    if let (Some(runs), Command::Part(day, part)) = (options.bench, &command) {
+        let bench = runner.bench(day, part, runs)?;
+        print!("{}", bench);
+        return Ok(());
+    }
+
+    let single = matches!(command, Command::Part(_, _));
+    let mut reports = match command {
+        Command::Help => {
+            println!("{}\n\nRegistered days:\n{}", cli::USAGE, runner.listing());
+            return Ok(());
+        }
+        Command::Disasm(_) | Command::Debug(_) | Command::Trace(_, _) => {
+            unreachable!("Intcode commands run before the runner is built")
+        }
+        Command::All => runner.run_all(),
+        Command::Day(day) => runner.run_day(&day),
+        Command::Part(day, part) => runner.run(&day, &part).map(|report| vec![report]),
+    }?;
+
+    if options.record {
+        let recorded = runner.record(&mut reports)?;
+        eprintln!("recorded {} answers", recorded);
+    }
+
+    if single && !options.record && reports.len() == 1 {
+        print_single(reports.into_iter().next().expect("one report"))
+    } else {
+        summarize(&reports)
+    }
+}
+
+/// Loads an Intcode program from `path`, or from stdin when `-`.
+fn program(path: &str) -> Result<Registers, Error> {
+    let source = match path {
+        "-" => input::Source::stdin()?,
+        path => input::Source::File(path.into()),
+    };
+    Registers::from_input(&source.read("", "")?)
+}
+
+/// Runs a debugger session on the program at `path`, with commands read from
+/// stdin. The prompt is only shown to a terminal so piped sessions stay clean.
+fn debug(path: &str, options: &cli::Options) -> Result<(), Error> {
+    if path == "-" {
+        return Err(Error::Usage(
+            "debug reads commands from stdin, so the program must come from a file".to_owned(),
+        ));
+    }
+    let stdin = io::stdin();
+    let prompt = if stdin.is_terminal() { "(debug) " } else { "" };
+    Debugger::new(load(path, options)?)
+        .run(stdin.lock(), io::stdout().lock(), prompt)
+        .map_err(|e| Error::runtime_from("Debugger session I/O failed", e))
+}
+
+/// Runs the program at `path` with stdin and stdout as its devices, tracing
+/// the instructions `options` select to `file`.
+fn run_trace(path: &str, file: &str, options: &cli::Options) -> Result<(), Error> {
+    let filter = trace::Filter::parse(options.addresses.as_deref(), &options.ops)?;
+    let mut machine = load(path, options)?;
+    let out = File::create(file).map_err(|e| Error::input(file, e))?;
+    let mut tracer = trace::Writer::new(BufWriter::new(out), filter);
+    let res = loop {
+        match machine.step_traced(&mut tracer) {
+            Run::Continue(_) => {}
+            Run::Output(value) => Stdio.output(value),
+            Run::NeedsInput => match Stdio.input() {
+                Some(value) => machine.feed(value),
+                None => break Run::NeedsInput,
+            },
+            res => break res,
+        }
+    };
+    tracer.finish().map_err(|e| Error::input(file, e))?;
+    match res {
+        Run::Finished => Ok(()),
+        Run::Error(e) => Err(Error::runtime_from("Intcode program failed", e)),
+        Run::Looped { position, steps } => Err(Error::runtime(format!(
+            "Intcode program loops forever at position {} after {} steps",
+            position, steps
+        ))),
+        Run::Exhausted { position, steps } => Err(Error::runtime(format!(
+            "Intcode program did not halt within {} steps, stopped at position {}",
+            steps, position
+        ))),
+        _ => Err(Error::runtime("Intcode program ran out of input")),
+    }
+}
+
+/// Loads the Intcode program at `path` with a watchdog that stops it once it
+/// loops or spends the budget `options` give.
+fn load(path: &str, options: &cli::Options) -> Result<Machine, Error> {
+    let watchdog = Watchdog::new()
+        .with_budget(options.budget.unwrap_or(STEP_BUDGET))
+        .with_loop_detection();
+    Ok(Machine::new(program(path)?).with_watchdog(watchdog))
+}
+
+/// A lone part prints just its answer so it can be piped elsewhere, with any
+/// verification problem reported on stderr.
+fn print_single(report: Report) -> Result<(), Error> {
+    let failed = report.failed();
+    let status = report.status();
+    let answer = report.result?;
+    println!("{}", answer);
+    if failed {
+        eprintln!("{}", status);
+        process::exit(FAILED_PARTS);
+    }
+    Ok(())
+}
+
+fn summarize(reports: &[Report]) -> Result<(), Error> {
+    let summary = Summary(reports);
+    print!("{}", summary);
+    if summary.failures() > 0 {
+        process::exit(FAILED_PARTS);
     }
+    Ok(())
 }
@@ -1,45 +1,45 @@
-use std::u64;
-
 use crate::challenge;
+use crate::error::Error;
 use crate::input;
 
-pub struct Challenge {}
+pub struct Challenge;
 
 impl challenge::Challenge for Challenge {
-    fn run(&mut self, part: &str) -> challenge::ChallengeResult {
-        match part {
-            "one" => self.part_one(),
-            "two" => self.part_two(),
-            _ => Err(challenge::Err::MissingPart(part.to_owned())),
-        }
-    }
+    const DAY: u32 = 1;
+    const TITLE: &'static str = "The Tyranny of the Rocket Equation";
+    const PARTS: &'static [challenge::Part] = &[
+        challenge::Part {
+            name: "one",
+            params: &[],
+            prepare: part_one,
+        },
+        challenge::Part {
+            name: "two",
+            params: &[],
+            prepare: part_two,
+        },
+    ];
 }
 
-impl Challenge {
-    pub fn new() -> Self {
-        Self {}
-    }
-
-    fn part_one(&mut self) -> challenge::ChallengeResult {
-        input::string("1", "1.txt")
-            .map_err(|e| input::read_error("one", "one", "1.txt", e))
-            .and_then(|s| calculate_fuel(&mut s.lines(), fuel_requirement))
-            .map(|total| println!("{}", total))
-    }
+fn part_one(source: &input::Source, _params: &input::Params) -> Result<challenge::Solver, Error> {
+    let masses = source.read("1", "1.txt")?.lines()?;
+    Ok(Box::new(move || {
+        Ok(calculate_fuel(&masses, fuel_requirement).into())
+    }))
+}
 
-    fn part_two(&mut self) -> challenge::ChallengeResult {
-        input::string("1", "2.txt")
-            .map_err(|e| input::read_error("one", "two", "2.txt", e))
-            .and_then(|s| calculate_fuel(&mut s.lines(), recursive_fuel_requirement))
-            .map(|total| println!("{}", total))
-    }
+fn part_two(source: &input::Source, _params: &input::Params) -> Result<challenge::Solver, Error> {
+    let masses = source.read("1", "2.txt")?.lines()?;
+    Ok(Box::new(move || {
+        Ok(calculate_fuel(&masses, recursive_fuel_requirement).into())
+    }))
 }
 
-fn fuel_requirement(mass: u64) -> u64 {
-    (mass / 3).checked_sub(2).unwrap_or(0)
+pub fn fuel_requirement(mass: u64) -> u64 {
+    (mass / 3).saturating_sub(2)
 }
 
-fn recursive_fuel_requirement(mass: u64) -> u64 {
+pub fn recursive_fuel_requirement(mass: u64) -> u64 {
     let mut total = 0;
     let mut adjustment = fuel_requirement(mass);
     while adjustment > 0 {
@@ -49,21 +49,8 @@ fn recursive_fuel_requirement(mass: u64) -> u64 {
     total
 }
 
-fn calculate_fuel<'a, I: Iterator<Item = &'a str>, F: Fn(u64) -> u64>(
-    modules: &mut I,
-    fuel_requirement: F,
-) -> Result<u64, challenge::Err> {
-    modules.try_fold(0, |sum, line| {
-        u64::from_str_radix(line, 10)
-            .map(|n| fuel_requirement(n) + sum)
-            .map_err(|e| {
-                challenge::Err::Failure(format!(
-                    "Failed to parse line containing {num:}: {msg:}",
-                    num = line,
-                    msg = e.to_string(),
-                ))
-            })
-    })
+pub fn calculate_fuel<F: Fn(u64) -> u64>(masses: &[u64], fuel_requirement: F) -> u64 {
+    masses.iter().map(|mass| fuel_requirement(*mass)).sum()
 }
 
 #[cfg(test)]
@@ -0,0 +1,160 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::answers::Verdict;
+use crate::challenge::Answer;
+use crate::error::Error;
+
+/// The outcome of running one part of one day.
+pub struct Report {
+    pub day: u32,
+    pub part: String,
+    pub result: Result<Answer, Error>,
+    pub verdict: Option<Verdict>,
+    pub elapsed: Duration,
+}
+
+impl Report {
+    pub fn failed(&self) -> bool {
+        self.result.is_err() || matches!(self.verdict, Some(Verdict::Fail(_)))
+    }
+
+    pub fn status(&self) -> String {
+        match (&self.result, &self.verdict) {
+            (Err(_), _) => "ERROR".to_owned(),
+            (Ok(_), Some(verdict)) => verdict.to_string(),
+            (Ok(_), None) => "ok".to_owned(),
+        }
+    }
+
+    /// A single-line rendering of the answer or error for the summary table.
+    fn cell(&self) -> String {
+        match &self.result {
+            Ok(Answer::Lines(lines)) => format!("({} lines, see below)", lines.len()),
+            Ok(answer) => match &self.verdict {
+                Some(Verdict::Fail(expected)) => {
+                    format!("{} (expected {})", answer, expected.replace('\n', "\\n"))
+                }
+                _ => answer.to_string(),
+            },
+            Err(e) => e.to_string(),
+        }
+    }
+}
+
+/// A table of reports, one row per part, followed by any multi-line answers
+/// that would not fit in a table cell.
+pub struct Summary<'a>(pub &'a [Report]);
+
+impl Summary<'_> {
+    pub fn failures(&self) -> usize {
+        self.0.iter().filter(|r| r.failed()).count()
+    }
+}
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = ["day", "part", "answer", "status", "time"];
+        let rows: Vec<[String; 5]> = self
+            .0
+            .iter()
+            .map(|r| {
+                [
+                    r.day.to_string(),
+                    r.part.clone(),
+                    r.cell(),
+                    r.status(),
+                    format!("{:.2?}", r.elapsed),
+                ]
+            })
+            .collect();
+
+        let mut widths = header.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        write_row(f, &widths, &header)?;
+        let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        write_row(f, &widths, &rule)?;
+        for row in &rows {
+            write_row(f, &widths, row)?;
+        }
+
+        for report in self.0 {
+            if let Ok(answer @ Answer::Lines(_)) = &report.result {
+                write!(
+                    f,
+                    "\nday {} part {}:\n{}\n",
+                    report.day, report.part, answer
+                )?;
+            }
+        }
+
+        let failures = self.failures();
+        if failures > 0 {
+            write!(f, "\n{} of {} parts failed\n", failures, self.0.len())?;
+        }
+        Ok(())
+    }
+}
+
+fn write_row<S: AsRef<str>>(f: &mut fmt::Formatter, widths: &[usize], cells: &[S]) -> fmt::Result {
+    let line: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, width)| format!("{:width$}", cell.as_ref(), width = width))
+        .collect();
+    writeln!(f, "{}", line.join("  ").trim_end())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn report(
+        day: u32,
+        part: &str,
+        result: Result<Answer, Error>,
+        verdict: Option<Verdict>,
+    ) -> Report {
+        Report {
+            day,
+            part: part.to_owned(),
+            result,
+            verdict,
+            elapsed: Duration::from_millis(1),
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        let reports = [
+            report(1, "one", Ok(Answer::Int(3478233)), Some(Verdict::Pass)),
+            report(1, "two", Ok(Answer::Int(7)), None),
+            report(
+                2,
+                "one",
+                Ok(Answer::Int(4)),
+                Some(Verdict::Fail("5".to_owned())),
+            ),
+            report(2, "two", Err(Error::runtime("boom")), Some(Verdict::New)),
+        ];
+        let summary = Summary(&reports);
+        assert_eq!(2, summary.failures());
+        assert_eq!(
+            "day  part  answer          status  time
+---  ----  --------------  ------  ------
+1    one   3478233         PASS    1.00ms
+1    two   7               ok      1.00ms
+2    one   4 (expected 5)  FAIL    1.00ms
+2    two   boom            ERROR   1.00ms
+
+2 of 4 parts failed
+",
+            summary.to_string()
+        );
+    }
+}
@@ -1,48 +1,456 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-use crate::challenge::Challenge;
+use crate::answers::Answers;
+use crate::bench::{Bench, Stats};
+use crate::challenge::{Day, Part};
+use crate::error::Error;
+use crate::input;
+use crate::report::Report;
 
 use crate::one;
 use crate::three;
 use crate::two;
 
+/// Day and part arguments may be given as a number (`1`, `01`), a word
+/// (`one`) or, for days, prefixed with `day` (`day1`); parts may also be
+/// given as a letter (`a`).
 pub struct Runner {
-    challenges: HashMap<&'static str, Box<dyn Challenge>>,
+    days: BTreeMap<u32, Day>,
+    input: input::Source,
+    params: input::Params,
 }
 
 impl Runner {
     pub fn new() -> Self {
-        let mut challenges = HashMap::new();
-        challenges.insert("one", Box::new(one::Challenge::new()) as Box<dyn Challenge>);
-        challenges.insert("two", Box::new(two::Challenge::new()) as Box<dyn Challenge>);
-        challenges.insert(
-            "three",
-            Box::new(three::Challenge::new()) as Box<dyn Challenge>,
-        );
-        Self { challenges }
-    }
-
-    pub fn run(&mut self, day: &str, part: &str) -> Result<(), Err> {
-        self.challenges
-            .get_mut(day)
-            .ok_or_else(|| {
-                Err::MissingChallenge(format!("No challenge for day {:} has been registered", day))
-            })?
-            .run(part)
-            .map_err(|e| Err::Failure(e.to_string()))
+        Self::with_input(input::Source::from_env())
+    }
+
+    /// A runner whose challenges all read their input from `input`. Answers
+    /// are only checked and recorded when `input` is a directory of inputs.
+    pub fn with_input(input: input::Source) -> Self {
+        Self::from_days(
+            &[
+                Day::of::<one::Challenge>(),
+                Day::of::<two::Challenge>(),
+                Day::of::<three::Challenge>(),
+            ],
+            input,
+        )
+    }
+
+    pub fn from_days(days: &[Day], input: input::Source) -> Self {
+        Self {
+            days: days.iter().map(|d| (d.number, *d)).collect(),
+            input,
+            params: input::Params::default(),
+        }
+    }
+
+    /// Passes `params` to every part. Answers are not checked or recorded
+    /// for runs with parameters, as they may change the expected answer.
+    pub fn with_params(mut self, params: input::Params) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn run(&self, day: &str, part: &str) -> Result<Report, Error> {
+        let (day, part) = self.resolve(day, part)?;
+        self.check_params(&[day.part(part)?])?;
+        let answers = self.answers(day.number)?;
+        Ok(self.run_part(&day, answers.as_ref(), part))
+    }
+
+    /// Runs every part of a single day, carrying on past failing parts.
+    pub fn run_day(&self, day: &str) -> Result<Vec<Report>, Error> {
+        let day = self.resolve_day(day)?;
+        self.check_params(&day.parts.iter().collect::<Vec<_>>())?;
+        Ok(self.run_days(&[day]))
+    }
+
+    /// Runs every part of every registered day in day order.
+    pub fn run_all(&self) -> Result<Vec<Report>, Error> {
+        let days: Vec<Day> = self.days.values().copied().collect();
+        self.check_params(&days.iter().flat_map(|d| d.parts).collect::<Vec<_>>())?;
+        Ok(self.run_days(&days))
+    }
+
+    /// A day whose answers cannot be loaded reports the failure for each of
+    /// its parts without running them, and the other days still run.
+    fn run_days(&self, days: &[Day]) -> Vec<Report> {
+        let mut reports = vec![];
+        for day in days {
+            let answers = self.answers(day.number);
+            for part in day.parts {
+                reports.push(match &answers {
+                    Ok(answers) => self.run_part(day, answers.as_ref(), part.name),
+                    Err(e) => Report {
+                        day: day.number,
+                        part: part.name.to_owned(),
+                        result: Err(Error::runtime(e.to_string())),
+                        verdict: None,
+                        elapsed: Duration::ZERO,
+                    },
+                });
+            }
+        }
+        reports
+    }
+
+    /// Runs a single part `runs` times, timing the preparation of its input
+    /// separately from solving it.
+    pub fn bench(&self, day: &str, part: &str, runs: usize) -> Result<Bench, Error> {
+        let (day, part) = self.resolve(day, part)?;
+        let part = day.part(part)?;
+        self.check_params(&[part])?;
+        let mut prepare = Vec::with_capacity(runs);
+        let mut solve = Vec::with_capacity(runs);
+        let mut answer = None;
+        for _ in 0..runs {
+            let start = Instant::now();
+            let solver = (part.prepare)(&self.input, &self.params)?;
+            prepare.push(start.elapsed());
+
+            let start = Instant::now();
+            answer = Some(solver()?);
+            solve.push(start.elapsed());
+        }
+
+        match (answer, Stats::new(&prepare), Stats::new(&solve)) {
+            (Some(answer), Some(prepare), Some(solve)) => Ok(Bench {
+                day: day.number,
+                part: part.name.to_owned(),
+                runs,
+                answer,
+                prepare,
+                solve,
+            }),
+            _ => Err(Error::Usage("Cannot benchmark zero runs".to_owned())),
+        }
+    }
+
+    /// Writes the answers of every successful report to its day's answers
+    /// file, returning how many were recorded. Each recorded report is then
+    /// checked again, against the answer it just saved.
+    pub fn record(&self, reports: &mut [Report]) -> Result<usize, Error> {
+        let dir = self.answers_dir().ok_or_else(|| {
+            Error::Usage(
+                "Answers can only be recorded for inputs read from an input directory \
+                 without parameters"
+                    .to_owned(),
+            )
+        })?;
+        let mut recorded = 0;
+        let mut days: Vec<u32> = reports.iter().map(|r| r.day).collect();
+        days.dedup();
+        for day in days {
+            let mut answers = Answers::load(dir, day)?;
+            for report in reports.iter_mut().filter(|r| r.day == day) {
+                if let Ok(answer) = &report.result {
+                    answers.record(&report.part, answer);
+                    report.verdict = Some(answers.check(&report.part, answer));
+                    recorded += 1;
+                }
+            }
+            answers.save()?;
+        }
+        Ok(recorded)
+    }
+
+    fn resolve_day(&self, day: &str) -> Result<Day, Error> {
+        parse_day(day)
+            .and_then(|d| self.days.get(&d).copied())
+            .ok_or_else(|| Error::MissingDay {
+                day: day.to_owned(),
+                registered: self.listing(),
+            })
+    }
+
+    fn resolve(&self, day: &str, part: &str) -> Result<(Day, &'static str), Error> {
+        let day = self.resolve_day(day)?;
+        let name = match day.part(part) {
+            Ok(found) => found.name,
+            Err(missing) => {
+                parse_part(part)
+                    .and_then(|n| day.parts.get(n - 1))
+                    .ok_or(missing)?
+                    .name
+            }
+        };
+        Ok((day, name))
+    }
+
+    /// Refuses parameters that none of `parts` read.
+    fn check_params(&self, parts: &[&Part]) -> Result<(), Error> {
+        match self
+            .params
+            .names()
+            .find(|name| !parts.iter().any(|part| part.params.contains(name)))
+        {
+            Some(name) => Err(Error::Usage(format!(
+                "No part run reads the parameter {}",
+                name
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// The directory answers are recorded in, unless this run's input or
+    /// parameters differ from the ones the answers were recorded with.
+    fn answers_dir(&self) -> Option<&Path> {
+        self.input.dir().filter(|_| self.params.is_empty())
+    }
+
+    /// The recorded answers for `day`, if this run can be checked against
+    /// them.
+    fn answers(&self, day: u32) -> Result<Option<Answers>, Error> {
+        self.answers_dir()
+            .map(|dir| Answers::load(dir, day))
+            .transpose()
+    }
+
+    fn run_part(&self, day: &Day, answers: Option<&Answers>, part: &str) -> Report {
+        let start = Instant::now();
+        let result = day.run(part, &self.input, &self.params);
+        let elapsed = start.elapsed();
+        Report {
+            day: day.number,
+            part: part.to_owned(),
+            verdict: match (&result, answers) {
+                (Ok(answer), Some(answers)) => Some(answers.check(part, answer)),
+                _ => None,
+            },
+            result,
+            elapsed,
+        }
+    }
+
+    /// One line per registered day listing its title and parts.
+    pub fn listing(&self) -> String {
+        self.days
+            .values()
+            .map(|d| {
+                format!(
+                    "  {:>2}: {} ({})",
+                    d.number,
+                    d.title,
+                    d.part_names().join(", ")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 }
 
-pub enum Err {
-    MissingChallenge(String),
-    Failure(String),
+impl Default for Runner {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl ToString for Err {
-    fn to_string(&self) -> String {
-        match self {
-            Self::MissingChallenge(s) => s.clone(),
-            Self::Failure(s) => s.clone(),
+const NUMBER_WORDS: [&str; 25] = [
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+    "twenty",
+    "twenty-one",
+    "twenty-two",
+    "twenty-three",
+    "twenty-four",
+    "twenty-five",
+];
+
+/// Parses `1`, `01`, `one` or `day1` as day 1.
+fn parse_day(id: &str) -> Option<u32> {
+    let id = id.trim().to_lowercase();
+    let id = id.strip_prefix("day").unwrap_or(&id);
+    parse_number(id.trim_start_matches(['-', '_', ' ']))
+}
+
+/// Parses `1`, `one` or `a` as part 1.
+fn parse_part(id: &str) -> Option<usize> {
+    let id = id.trim().to_lowercase();
+    match id.as_bytes() {
+        [c @ b'a'..=b'z'] => Some((c - b'a') as usize + 1),
+        _ => parse_number(&id).map(|n| n as usize),
+    }
+}
+
+fn parse_number(id: &str) -> Option<u32> {
+    id.parse::<u32>()
+        .ok()
+        .or_else(|| {
+            NUMBER_WORDS
+                .iter()
+                .position(|w| *w == id || w.replace('-', "") == id)
+                .map(|i| i as u32 + 1)
+        })
+        .filter(|n| *n > 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::answers::Verdict;
+    use crate::challenge::{Answer, Part, Solver};
+    use std::fs;
+
+    #[test]
+    fn test_parse_day() {
+        for id in &["1", "01", "one", "One", "day1", "day01", "day-one"] {
+            assert_eq!(Some(1), parse_day(id), "{}", id);
+        }
+        assert_eq!(Some(21), parse_day("twenty-one"));
+        assert_eq!(Some(25), parse_day("day25"));
+        assert_eq!(None, parse_day("0"));
+        assert_eq!(None, parse_day("first"));
+    }
+
+    #[test]
+    fn test_parse_part() {
+        for id in &["1", "one", "a", "A"] {
+            assert_eq!(Some(1), parse_part(id), "{}", id);
         }
+        assert_eq!(Some(2), parse_part("b"));
+        assert_eq!(Some(2), parse_part("02"));
+        assert_eq!(None, parse_part("?"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let runner = Runner::new();
+        let resolve = |day, part| {
+            runner
+                .resolve(day, part)
+                .map(|(day, part)| (day.number, part))
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(Ok((1, "two")), resolve("01", "b"));
+        assert_eq!(Ok((2, "one")), resolve("day2", "1"));
+        assert!(runner.resolve("three", "two").is_err());
+        assert!(runner.resolve("nine", "one").is_err());
+    }
+
+    #[test]
+    fn test_record() {
+        fn prepare(_: &input::Source, _: &input::Params) -> Result<Solver, Error> {
+            Ok(Box::new(|| Ok(Answer::Int(709))))
+        }
+        let day = Day {
+            number: 3,
+            title: "Test",
+            parts: &[Part {
+                name: "one",
+                params: &[],
+                prepare,
+            }],
+        };
+        let dir = std::env::temp_dir().join(format!("aoc2k19-record-{}", std::process::id()));
+        fs::create_dir_all(dir.join("3")).expect("Failed to create input dir");
+        fs::write(dir.join("3").join("answers"), "one: 700\n").expect("Failed to write answers");
+
+        let runner = Runner::from_days(&[day], input::Source::Dir(dir.clone()));
+        let mut reports = runner.run_day("3").expect("Failed to run day");
+        assert_eq!(Some(Verdict::Fail("700".to_owned())), reports[0].verdict);
+
+        assert_eq!(1, runner.record(&mut reports).expect("Failed to record"));
+        assert_eq!(Some(Verdict::Pass), reports[0].verdict);
+        assert!(!reports[0].failed());
+        let saved = fs::read_to_string(dir.join("3").join("answers"));
+        fs::remove_dir_all(&dir).expect("Failed to clean up");
+        assert_eq!("one: 709\n", saved.expect("Failed to read answers"));
+    }
+
+    #[test]
+    fn test_check_params() {
+        fn prepare(_: &input::Source, params: &input::Params) -> Result<Solver, Error> {
+            let x = params.get("x", 1)?;
+            Ok(Box::new(move || Ok(Answer::Int(x))))
+        }
+        let day = Day {
+            number: 1,
+            title: "Test",
+            parts: &[
+                Part {
+                    name: "one",
+                    params: &["x"],
+                    prepare,
+                },
+                Part {
+                    name: "two",
+                    params: &[],
+                    prepare,
+                },
+            ],
+        };
+        let runner = |assignment| {
+            Runner::from_days(&[day], input::Source::Text(String::new()))
+                .with_params(input::Params::parse(&[assignment]).expect("Bad params"))
+        };
+
+        let report = runner("x=5").run("1", "one").expect("Failed to run part");
+        assert_eq!(Some(Answer::Int(5)), report.result.ok());
+        assert_eq!(2, runner("x=5").run_all().expect("Failed to run").len());
+        assert!(matches!(
+            runner("x=5").run("1", "two"),
+            Err(Error::Usage(_))
+        ));
+        assert!(matches!(runner("y=5").run_day("1"), Err(Error::Usage(_))));
+        assert!(matches!(
+            runner("y=5").bench("1", "one", 1),
+            Err(Error::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn test_bad_answers() {
+        fn prepare(_: &input::Source, _: &input::Params) -> Result<Solver, Error> {
+            Ok(Box::new(|| Ok(Answer::Int(12))))
+        }
+        const PARTS: &[Part] = &[
+            Part {
+                name: "one",
+                params: &[],
+                prepare,
+            },
+            Part {
+                name: "two",
+                params: &[],
+                prepare,
+            },
+        ];
+        let day = |number| Day {
+            number,
+            title: "Test",
+            parts: PARTS,
+        };
+        let dir = std::env::temp_dir().join(format!("aoc2k19-bad-answers-{}", std::process::id()));
+        fs::create_dir_all(dir.join("1")).expect("Failed to create input dir");
+        fs::write(dir.join("1").join("answers"), "garbage\n").expect("Failed to write answers");
+
+        let runner = Runner::from_days(&[day(1), day(2)], input::Source::Dir(dir.clone()));
+        let reports = runner.run_all();
+        fs::remove_dir_all(&dir).expect("Failed to clean up");
+        let reports = reports.expect("Failed to run days");
+        let outcomes: Vec<(u32, bool)> = reports.iter().map(|r| (r.day, r.failed())).collect();
+        assert_eq!(vec![(1, true), (1, true), (2, false), (2, false)], outcomes);
+        assert!(matches!(&reports[0].result, Err(e) if e.to_string().contains("answers")));
     }
 }
@@ -6,36 +6,42 @@ use std::iter;
 use std::str::FromStr;
 
 use crate::challenge;
+use crate::error;
 use crate::input;
 
-pub struct Challenge {}
+pub struct Challenge;
 
 impl challenge::Challenge for Challenge {
-    fn run(&mut self, part: &str) -> challenge::ChallengeResult {
-        match part {
-            "one" => self.run_one(),
-            p => Err(challenge::Err::MissingPart(format!(
-                "Part {} is not implemented",
-                p
-            ))),
-        }
-    }
+    const DAY: u32 = 3;
+    const TITLE: &'static str = "Crossed Wires";
+    const PARTS: &'static [challenge::Part] = &[challenge::Part {
+        name: "one",
+        params: &[],
+        prepare: part_one,
+    }];
 }
 
-impl Challenge {
-    pub fn new() -> Self {
-        Self {}
+fn part_one(
+    source: &input::Source,
+    _params: &input::Params,
+) -> Result<challenge::Solver, error::Error> {
+    let wires: Vec<Vec<Motion>> = source.read("3", "1.txt")?.csv_lines()?;
+    if wires.len() < 2 {
+        return Err(error::Error::parse(
+            "Failed to parse wires",
+            MissingWiresError(wires.len()),
+        ));
     }
 
-    fn run_one(&self) -> challenge::ChallengeResult {
-        input::string("3", "1.txt")
-            .map_err(|e| input::read_error("3", "1", "1.txt", e))
-            .map(|s| println!("{}", calculate(&*s)))
-    }
+    Ok(Box::new(move || {
+        calculate(&wires)
+            .map(challenge::Answer::from)
+            .ok_or_else(|| error::Error::runtime("The wires never cross"))
+    }))
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
-enum Direction {
+pub enum Direction {
     U,
     R,
     D,
@@ -57,7 +63,7 @@ impl FromStr for Direction {
 }
 
 #[derive(Debug)]
-struct ParseDirectionError(String);
+pub struct ParseDirectionError(String);
 
 impl Error for ParseDirectionError {}
 impl fmt::Display for ParseDirectionError {
@@ -67,11 +73,11 @@ impl fmt::Display for ParseDirectionError {
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
-struct Motion(Direction, u64);
+pub struct Motion(pub Direction, pub u64);
 
 impl Motion {
-    fn expand(&self) -> impl Iterator<Item = Self> {
-        iter::repeat(Self(self.0, 1)).take(self.1 as usize)
+    pub fn expand(&self) -> impl Iterator<Item = Self> {
+        iter::repeat_n(Self(self.0, 1), self.1 as usize)
     }
 }
 
@@ -79,7 +85,7 @@ impl FromStr for Motion {
     type Err = ParseMotionError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (dir, rest) = s.split_at(1);
+        let (dir, rest) = s.split_at(s.char_indices().nth(1).map_or(s.len(), |(i, _)| i));
 
         str::parse::<Direction>(dir)
             .map_err(|e| ParseMotionError(s.to_owned(), Box::new(e)))
@@ -92,29 +98,43 @@ impl FromStr for Motion {
 }
 
 #[derive(Debug)]
-struct ParseMotionError(String, Box<dyn Error>);
+pub struct ParseMotionError(String, Box<dyn Error>);
+
+impl Error for ParseMotionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.1.as_ref())
+    }
+}
 
-impl Error for ParseMotionError {}
 impl fmt::Display for ParseMotionError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "Motion parse failed on string \"{string:}\": {cause:}",
-            string = self.0,
-            cause = self.1
+            "Motion parse failed on string \"{string:}\"",
+            string = self.0
         )
     }
 }
 
+#[derive(Debug)]
+pub struct MissingWiresError(usize);
+
+impl Error for MissingWiresError {}
+impl fmt::Display for MissingWiresError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Expected two wires but found {}", self.0)
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Hash)]
-struct Pos(i64, i64);
+pub struct Pos(pub i64, pub i64);
 
 impl Pos {
-    fn origin() -> Self {
+    pub fn origin() -> Self {
         Self(0, 0)
     }
 
-    fn mv(&self, mot: &Motion) -> Self {
+    pub fn mv(&self, mot: &Motion) -> Self {
         match mot.0 {
             Direction::U => Self(self.0, self.1 + mot.1 as i64),
             Direction::R => Self(self.0 + mot.1 as i64, self.1),
@@ -123,37 +143,26 @@ impl Pos {
         }
     }
 
-    fn dist(&self, other: &Self) -> u64 {
+    pub fn dist(&self, other: &Self) -> u64 {
         ((other.0 - self.0).abs() + (other.1 - self.1).abs()) as u64
     }
 }
 
-fn calculate(input: &str) -> u64 {
-    let parsed_wires = input
-        .lines()
-        .map(|s| s.split(",").map(|ms| ms.parse()).collect())
-        .collect::<Result<Vec<Vec<Motion>>, ParseMotionError>>();
-
-    match parsed_wires {
-        Err(e) => panic!("{}", e.description()),
-        Ok(mots) => {
-            let mots = mots.get(0..=1).expect("No instruction streams");
-
-            let one = positions(&mots[0]);
-            let two = positions(&mots[1]);
-
-            let zero = Pos::origin();
-            let intersect = one.intersection(&two);
-            intersect
-                .into_iter()
-                .map(|p| p.dist(&zero))
-                .min()
-                .expect("no minimum")
-        }
-    }
+/// The distance from the origin to the closest crossing of the first two
+/// wires, if they cross at all.
+pub fn calculate(wires: &[Vec<Motion>]) -> Option<u64> {
+    let mots = wires.get(0..=1)?;
+
+    let one = positions(&mots[0]);
+    let two = positions(&mots[1]);
+
+    let zero = Pos::origin();
+    let intersect = one.intersection(&two);
+    intersect.into_iter().map(|p| p.dist(&zero)).min()
 }
 
-fn positions(motions: &Vec<Motion>) -> HashSet<Pos> {
+/// Every grid position a wire passes through, excluding the origin.
+pub fn positions(motions: &[Motion]) -> HashSet<Pos> {
     motions
         .iter()
         .flat_map(Motion::expand)
@@ -168,24 +177,30 @@ fn positions(motions: &Vec<Motion>) -> HashSet<Pos> {
 mod test {
     use super::*;
 
+    fn parse(wires: &str) -> Vec<Vec<Motion>> {
+        input::Input::new("wires", wires)
+            .csv_lines()
+            .expect("Failed to parse wires")
+    }
+
     #[test]
     fn test_calculate() {
         let input_str = "R8,U5,L5,D3
 U7,R6,D4,L4";
-        let expectation = 6;
-        let result = calculate(input_str);
+        let expectation = Some(6);
+        let result = calculate(&parse(input_str));
         assert_eq!(expectation, result);
 
         let input_str = "R75,D30,R83,U83,L12,D49,R71,U7,L72
 U62,R66,U55,R34,D71,R55,D58,R83";
-        let expectation = 159;
-        let result = calculate(input_str);
+        let expectation = Some(159);
+        let result = calculate(&parse(input_str));
         assert_eq!(expectation, result);
 
         let input_str = "R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51
 U98,R91,D20,R16,D67,R40,U7,R15,U6,R7";
-        let expectation = 135;
-        let result = calculate(input_str);
+        let expectation = Some(135);
+        let result = calculate(&parse(input_str));
         assert_eq!(expectation, result);
     }
 
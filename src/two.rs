@@ -1,307 +1,131 @@
 use crate::challenge;
-use std::error::Error;
-use std::fmt;
-use std::usize;
-
+use crate::error;
 use crate::input;
+use crate::intcode::watchdog::Watchdog;
+use crate::intcode::{Machine, Run};
 
-pub struct Challenge {}
+pub struct Challenge;
 
 impl challenge::Challenge for Challenge {
-    fn run(&mut self, part: &str) -> challenge::ChallengeResult {
-        match part {
-            "one" => self.one(),
-            _ => Err(challenge::Err::MissingPart(format!(
-                "No part {part:} available",
-                part = part
-            ))),
-        }
-    }
-}
-
-impl Challenge {
-    pub fn new() -> Self {
-        Self {}
-    }
-
-    fn one(&mut self) -> challenge::ChallengeResult {
-        let script =
-            input::string("2", "1.txt").map_err(|e| input::read_error("2", "1", "1.txt", e))?;
-
-        let mut registers =
-            Registers::from_program(&*script).map_err(|e| challenge::Err::Failure(e))?;
-
-        registers.0[1] = 12;
-        registers.0[2] = 2;
-
-        match run_script(&mut registers) {
-            Run::Finished => report_result(&registers),
-            Run::Error(e) => Err(challenge::Err::Failure(e.description().to_owned())),
-            _ => Err(challenge::Err::Failure(
-                "Script did not run to completion".to_owned(),
-            )),
-        }
-    }
-}
-
-fn run_script(registers: &mut Registers) -> Run {
-    let mut state = EvalStep::new(0);
-    loop {
-        match run_step(&state, registers) {
-            Run::Continue(next) => state = next,
-            res => return res,
-        }
-    }
-}
-
-fn run_step(state: &EvalStep, registers: &mut Registers) -> Run {
-    match state.eval(registers) {
-        Err(e) => Run::Error(RunError(e.description().to_owned())),
-        Ok((_, OpCode::Stop)) => Run::Finished,
-        Ok((next, op)) => match op.apply(registers) {
-            Err(e) => Run::Error(RunError(e.description().to_owned())),
-            _ => Run::Continue(next),
+    const DAY: u32 = 2;
+    const TITLE: &'static str = "1202 Program Alarm";
+    const PARTS: &'static [challenge::Part] = &[
+        challenge::Part {
+            name: "one",
+            params: &["budget"],
+            prepare: part_one,
         },
-    }
-}
-
-fn report_result(regs: &Registers) -> challenge::ChallengeResult {
-    regs.at(0)
-        .map(|val| println!("Final value: {}", val))
-        .map_err(|e| {
-            challenge::Err::Failure(format!(
-                "Cannot report result from invalid registers: {}",
-                e
-            ))
-        })
-}
-
-enum Run {
-    Finished,
-    Continue(EvalStep),
-    Error(RunError),
-}
-
-#[derive(Debug)]
-struct RunError(String);
-
-impl Error for RunError {}
-impl fmt::Display for RunError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Error running script: {}", self.0)
-    }
-}
-
-#[derive(Debug, PartialEq, Eq)]
-enum OpCode {
-    Stop,
-    Add(usize, usize, usize),
-    Mul(usize, usize, usize),
-}
-
-impl OpCode {
-    fn parse(position: usize, registers: &Registers) -> Result<Self, OpCodeParseError> {
-        let code = registers
-            .at(position)
-            .map_err(|e| OpCodeParseError(e.description().to_owned()))?;
-
-        match code {
-            1 | 2 => registers
-                .range(position + 1, position + 3)
-                .map_err(|e| OpCodeParseError(e.description().to_owned()))
-                .map(|nums| {
-                    if *code == 1 {
-                        Self::Add(nums[0], nums[1], nums[2])
-                    } else {
-                        Self::Mul(nums[0], nums[1], nums[2])
-                    }
-                }),
-            99 => Ok(OpCode::Stop),
-            c => Err(OpCodeParseError(format!(
-                "Invalid op code {code:} at position {pos:}",
-                code = c,
-                pos = position
-            ))),
-        }
-    }
-
-    fn apply(&self, registers: &mut Registers) -> Result<(), OpApplicationError> {
-        match self {
-            Self::Stop => Err(OpApplicationError::Stop),
-            Self::Add(xloc, yloc, resloc) => {
-                let x = registers
-                    .at(*xloc)
-                    .map_err(|e| OpApplicationError::Arithmetic(e.description().to_owned()))?;
-
-                let y = registers
-                    .at(*yloc)
-                    .map_err(|e| OpApplicationError::Arithmetic(e.description().to_owned()))?;
-
-                let sum = x + y;
-
-                registers
-                    .set(*resloc, sum)
-                    .map_err(|e| OpApplicationError::Arithmetic(e.description().to_owned()))
-            }
-            Self::Mul(xloc, yloc, resloc) => {
-                let x = registers
-                    .at(*xloc)
-                    .map_err(|e| OpApplicationError::Arithmetic(e.description().to_owned()))?;
-                let y = registers
-                    .at(*yloc)
-                    .map_err(|e| OpApplicationError::Arithmetic(e.description().to_owned()))?;
-
-                let sum = x * y;
-
-                registers
-                    .set(*resloc, sum)
-                    .map_err(|e| OpApplicationError::Arithmetic(e.description().to_owned()))
+        challenge::Part {
+            name: "two",
+            params: &["budget", "target"],
+            prepare: part_two,
+        },
+    ];
+}
+
+/// The output part two searches for, overridable with `--param target=<n>`.
+const TARGET_OUTPUT: i64 = 19_690_720;
+
+/// The most instructions a single run may take, overridable with
+/// `--param budget=<n>`. Some nouns and verbs rewrite the program into one
+/// that never halts.
+const STEP_BUDGET: u64 = 100_000;
+
+fn part_one(
+    source: &input::Source,
+    params: &input::Params,
+) -> Result<challenge::Solver, error::Error> {
+    let program = load(source, params)?;
+
+    Ok(Box::new(move || {
+        run_with(&program, 12, 2).map(challenge::Answer::from)
+    }))
+}
+
+fn part_two(
+    source: &input::Source,
+    params: &input::Params,
+) -> Result<challenge::Solver, error::Error> {
+    let program = load(source, params)?;
+    let target = params.get("target", TARGET_OUTPUT)?;
+
+    Ok(Box::new(move || {
+        find_inputs(&program, target)?
+            .map(|(noun, verb)| challenge::Answer::from(100 * noun + verb))
+            .ok_or_else(|| {
+                error::Error::runtime(format!("No noun and verb produce the output {}", target))
+            })
+    }))
+}
+
+/// Loads the program with a watchdog that stops it if it loops or runs past
+/// its budget.
+fn load(source: &input::Source, params: &input::Params) -> Result<Machine, error::Error> {
+    let watchdog = Watchdog::new()
+        .with_budget(params.get("budget", STEP_BUDGET)?)
+        .with_loop_detection();
+    Ok(Machine::from_input(&source.read("2", "1.txt")?)?.with_watchdog(watchdog))
+}
+
+/// Runs `program` with `noun` and `verb` in addresses 1 and 2, returning the
+/// value left in address 0.
+fn run_with(program: &Machine, noun: i64, verb: i64) -> Result<i64, error::Error> {
+    let (machine, res) = start(program, noun, verb)?;
+    output(&machine, res)
+}
+
+/// Runs a copy of `program` with `noun` and `verb` in addresses 1 and 2,
+/// returning the machine and how it stopped.
+fn start(program: &Machine, noun: i64, verb: i64) -> Result<(Machine, Run), error::Error> {
+    let mut machine = program.clone();
+    machine
+        .poke(1, noun)
+        .and_then(|_| machine.poke(2, verb))
+        .map_err(|e| error::Error::runtime_from("Failed to set the noun and verb", e))?;
+    let res = machine.run();
+    Ok((machine, res))
+}
+
+/// The value `machine` left in address 0, if it stopped with `res` by
+/// halting.
+fn output(machine: &Machine, res: Run) -> Result<i64, error::Error> {
+    match res {
+        Run::Finished => machine.peek(0).map_err(|e| {
+            error::Error::runtime_from("Cannot report result from invalid registers", e)
+        }),
+        Run::Error(e) => Err(error::Error::runtime_from(
+            "Gravity assist program failed",
+            e,
+        )),
+        Run::Looped { position, .. } => Err(error::Error::runtime(format!(
+            "Gravity assist program loops forever at position {}",
+            position
+        ))),
+        Run::Exhausted { steps, .. } => Err(error::Error::runtime(format!(
+            "Gravity assist program did not halt within {} steps",
+            steps
+        ))),
+        _ => Err(error::Error::runtime("Script did not run to completion")),
+    }
+}
+
+/// Searches every noun and verb in `0..=99` for the pair that makes
+/// `program` output `target`. Pairs the program fails or loops forever on
+/// are skipped, but one that spends the whole step budget stops the search,
+/// as a bigger budget might have let it halt with the answer.
+fn find_inputs(program: &Machine, target: i64) -> Result<Option<(i64, i64)>, error::Error> {
+    for noun in 0..=99 {
+        for verb in 0..=99 {
+            let (machine, res) = start(program, noun, verb)?;
+            let exhausted = matches!(res, Run::Exhausted { .. });
+            match output(&machine, res) {
+                Ok(value) if value == target => return Ok(Some((noun, verb))),
+                Err(e) if exhausted => return Err(e),
+                _ => {}
             }
         }
     }
-}
-
-#[derive(Debug)]
-struct OpCodeParseError(String);
-
-impl Error for OpCodeParseError {}
-impl fmt::Display for OpCodeParseError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Failure parsing OpCode: {}", self.0)
-    }
-}
-
-#[derive(Debug)]
-enum OpApplicationError {
-    Stop,
-    Arithmetic(String),
-}
-
-impl Error for OpApplicationError {}
-impl fmt::Display for OpApplicationError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::Stop => write!(f, "Attempted to apply Stop op code"),
-            Self::Arithmetic(msg) => write!(f, "Math failed: {}", msg),
-        }
-    }
-}
-
-struct Registers(Vec<usize>);
-
-impl Registers {
-    fn from_program(program: &str) -> Result<Self, String> {
-        let mut regs = vec![];
-        let result: Result<(), ProgramParseError> =
-            program.lines().enumerate().try_for_each(|(line_no, line)| {
-                line.split(',')
-                    .enumerate()
-                    .map(|(col_no, num_string)| {
-                        usize::from_str_radix(num_string, 10).map_err(|_| ProgramParseError {
-                            line_no,
-                            col_no,
-                            bad_val: num_string.to_owned(),
-                        })
-                    })
-                    .collect::<Result<Vec<usize>, ProgramParseError>>()
-                    .map(|nums| {
-                        for num in nums {
-                            regs.push(num);
-                        }
-                    })
-            });
-
-        match result {
-            Err(e) => Err(e.description().to_string()),
-            _ => Ok(Self(regs)),
-        }
-    }
-
-    fn at(&self, num: usize) -> RegisterResult<&usize> {
-        self.0.get(num).ok_or_else(|| RegisterErr::Missing(num))
-    }
-
-    fn set(&mut self, num: usize, val: usize) -> RegisterResult<()> {
-        let len = self.0.len();
-        if len < num {
-            Err(RegisterErr::Insert(num))
-        } else {
-            self.0[num] = val;
-            Ok(())
-        }
-    }
-
-    fn range(&self, start: usize, end: usize) -> RegisterResult<&[usize]> {
-        self.0
-            .get(start..=end)
-            .ok_or_else(|| RegisterErr::MissingRange(start, end))
-    }
-}
-
-type RegisterResult<T> = Result<T, RegisterErr>;
-
-#[derive(Debug)]
-enum RegisterErr {
-    Insert(usize),
-    Missing(usize),
-    MissingRange(usize, usize),
-}
-
-impl Error for RegisterErr {}
-impl fmt::Display for RegisterErr {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::Insert(p) => write!(f, "Failed to insert at position {}", p),
-            Self::Missing(p) => write!(f, "No value at position {}", p),
-            Self::MissingRange(s, e) => write!(f, "Value in range [{}, {}] is missing", s, e),
-        }
-    }
-}
-
-#[derive(Debug)]
-struct ProgramParseError {
-    line_no: usize,
-    col_no: usize,
-    bad_val: String,
-}
-
-impl Error for ProgramParseError {}
-impl fmt::Display for ProgramParseError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Parsing failure at {},{}: {}",
-            self.line_no, self.col_no, self.bad_val
-        )
-    }
-}
-
-struct EvalStep {
-    position: usize,
-}
-
-impl EvalStep {
-    fn new(position: usize) -> Self {
-        Self { position }
-    }
-
-    fn eval(&self, registers: &Registers) -> EvalResult {
-        OpCode::parse(self.position, registers)
-            .map_err(|e| EvalError(e.description().to_owned()))
-            .map(|op| (Self::new(self.position + 4), op))
-    }
-}
-
-type EvalResult = Result<(EvalStep, OpCode), EvalError>;
-
-#[derive(Debug)]
-struct EvalError(String);
-
-impl Error for EvalError {}
-impl fmt::Display for EvalError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Error in evaluation: {}", self.0)
-    }
+    Ok(None)
 }
 
 #[cfg(test)]
@@ -309,63 +133,33 @@ mod test {
     use super::*;
 
     #[test]
-    fn test_run() {
-        let mut registers = Registers(vec![1, 0, 0, 0, 99]);
-        run_script(&mut registers);
-        assert_eq!(
-            [2, 0, 0, 0, 99],
-            *registers.range(0, 4).expect("test 1 failed")
-        );
-
-        let mut registers = Registers(vec![2, 3, 0, 3, 99]);
-        run_script(&mut registers);
-        assert_eq!(
-            [2, 3, 0, 6, 99],
-            *registers.range(0, 4).expect("test 2 failed")
-        );
-
-        let mut registers = Registers(vec![2, 4, 4, 5, 99, 0]);
-        run_script(&mut registers);
-        assert_eq!(
-            [2, 4, 4, 5, 99, 9801],
-            registers.range(0, 5).expect("test 3 failed")
-        );
-
-        let mut registers = Registers(vec![1, 1, 1, 4, 99, 5, 6, 0, 99]);
-        run_script(&mut registers);
-        assert_eq!(
-            [30, 1, 1, 4, 2, 5, 6, 0, 99],
-            registers.range(0, 8).expect("test 4 failed")
-        );
-    }
-
-    #[test]
-    fn test_op_parse() {
-        let registers = Registers(vec![1, 0, 0, 0, 99]);
-        let code = OpCode::parse(0, &registers).expect("Failed to parse add op code");
-        assert_eq!(OpCode::Add(0, 0, 0), code);
-
-        let registers = Registers(vec![2, 0, 0, 0, 99]);
-        let code = OpCode::parse(0, &registers).expect("Failed to parse mul op code");
-        assert_eq!(OpCode::Mul(0, 0, 0), code);
+    fn test_run_with() {
+        let program = Machine::from_program("1,0,0,0,99,0,0,0,0,0,70,65").expect("Bad program");
+        assert_eq!(Some(135), run_with(&program, 10, 11).ok());
+        assert_eq!(Some(140), run_with(&program, 10, 10).ok());
 
-        let registers = Registers(vec![99, 0, 0, 0, 99]);
-        let code = OpCode::parse(0, &registers).expect("Failed to parse stop op code");
-        assert_eq!(OpCode::Stop, code);
+        // JT #1, #0 spins forever, JT #0, #0 falls through to the halt.
+        let program = Machine::from_program("1105,0,0,99")
+            .expect("Bad program")
+            .with_watchdog(Watchdog::new().with_loop_detection());
+        assert!(run_with(&program, 1, 0).is_err());
+        assert_eq!(Some(1105), run_with(&program, 0, 0).ok());
     }
 
     #[test]
-    fn test_op_apply() {
-        let mut registers = Registers(vec![1, 0, 0, 0, 99]);
-        let code = OpCode::Add(0, 1, 3);
-        code.apply(&mut registers)
-            .expect("Addition application failed");
-        assert_eq!([1, 0, 0, 1, 99], registers.0[0..5]);
-
-        let mut registers = Registers(vec![1, 3, 0, 0, 99]);
-        let code = OpCode::Mul(0, 1, 3);
-        code.apply(&mut registers)
-            .expect("Multiplication application failed");
-        assert_eq!([1, 3, 0, 3, 99], registers.0[0..5]);
+    fn test_find_inputs() {
+        let program = Machine::from_program("1,0,0,0,99,0,0,0,0,0,70,65").expect("Bad program");
+        assert_eq!(Some((10, 11)), find_inputs(&program, 135).ok().flatten());
+        assert_eq!(Some(None), find_inputs(&program, 1000).ok());
+
+        // Pairs that loop are skipped, but running out of budget is an error.
+        let program = Machine::from_program("1105,0,0,99")
+            .expect("Bad program")
+            .with_watchdog(Watchdog::new().with_loop_detection());
+        assert_eq!(Some(None), find_inputs(&program, 7).ok());
+        let program = Machine::from_program("1,0,0,0,99,0,0,0,0,0,70,65")
+            .expect("Bad program")
+            .with_watchdog(Watchdog::new().with_budget(1));
+        assert!(find_inputs(&program, 135).is_err());
     }
 }
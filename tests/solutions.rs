@@ -0,0 +1,36 @@
+use aoc2k19::answers::Verdict;
+use aoc2k19::input::Input;
+use aoc2k19::intcode::{Machine, Run};
+use aoc2k19::three;
+use aoc2k19::Runner;
+
+#[test]
+fn test_recorded_answers() {
+    let reports = Runner::new().run_all().expect("Failed to run all days");
+    assert!(!reports.is_empty());
+    for report in reports {
+        assert!(
+            matches!(report.verdict, Some(Verdict::Pass)),
+            "day {} part {}: {}",
+            report.day,
+            report.part,
+            report.status()
+        );
+    }
+}
+
+#[test]
+fn test_intcode() {
+    let mut machine =
+        Machine::from_program("1,9,10,3,2,3,11,0,99,30,40,50").expect("Failed to parse program");
+    assert!(matches!(machine.run(), Run::Finished));
+    assert_eq!(3500, machine.peek(0).expect("No value at 0"));
+}
+
+#[test]
+fn test_wires() {
+    let wires = Input::new("wires", "R8,U5,L5,D3\nU7,R6,D4,L4")
+        .csv_lines()
+        .expect("Failed to parse wires");
+    assert_eq!(Some(6), three::calculate(&wires));
+}